@@ -0,0 +1,471 @@
+extern crate tdmath;
+
+use tdmath::Vector3;
+
+/// The basis of a free-form curve or surface, from `cstype`. The `bmatrix`, `cardinal`
+/// and `taylor` bases are not supported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Basis {
+    Bezier,
+    BSpline,
+}
+
+/// A control point of a curve or surface: a `v` position and its weight.
+#[derive(Debug, Clone, Copy)]
+pub struct ControlPoint {
+    pub p: Vector3,
+    pub w: f32,
+}
+
+/// A space curve from a `curv` statement.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    pub basis: Basis,
+    /// Set by `cstype rat`. Only then are control point weights used.
+    pub rational: bool,
+    pub degree: usize,
+    /// The parameter range of the curve.
+    pub start: f32,
+    pub end: f32,
+    pub control_points: Vec<ControlPoint>,
+    /// `parm u`: the knot vector of a B-spline, or the patch boundaries of a Bezier curve.
+    pub knots: Vec<f32>,
+}
+
+/// A curve in the parameter space of a surface, from a `curv2` statement.
+#[derive(Debug, Clone)]
+pub struct ParameterCurve {
+    pub basis: Basis,
+    pub rational: bool,
+    pub degree: usize,
+    /// `vp` points as (u, v, weight).
+    pub control_points: Vec<Vector3>,
+    pub knots: Vec<f32>,
+}
+
+/// Part of a parameter curve bounding a surface, from a `trim` or `hole` statement.
+#[derive(Debug, Clone)]
+pub struct TrimCurve {
+    pub start: f32,
+    pub end: f32,
+    pub curve: ParameterCurve,
+}
+
+/// A surface from a `surf` statement.
+#[derive(Debug, Clone)]
+pub struct Surface {
+    pub basis: Basis,
+    pub rational: bool,
+    pub degree_u: usize,
+    pub degree_v: usize,
+    pub start_u: f32,
+    pub end_u: f32,
+    pub start_v: f32,
+    pub end_v: f32,
+    /// Control points with `u` varying fastest.
+    pub control_points: Vec<ControlPoint>,
+    /// `parm u`
+    pub knots_u: Vec<f32>,
+    /// `parm v`
+    pub knots_v: Vec<f32>,
+    /// Outer boundary loops, one for each `trim` statement.
+    pub trims: Vec<Vec<TrimCurve>>,
+    /// Inner boundary loops, one for each `hole` statement.
+    pub holes: Vec<Vec<TrimCurve>>,
+    pub material: Option<String>,
+}
+
+// A point on a tessellated surface.
+pub(crate) struct SurfacePoint {
+    pub p: Vector3,
+    pub normal: Vector3,
+    // Position within the surface's parameter range, from 0 to 1 in each direction.
+    pub uv: Vector3,
+}
+
+// The full knot vector to evaluate `count` control points with, or `None` if the
+// knots do not fit. Bezier patch boundaries are expanded to an equivalent B-spline knot vector.
+fn knot_vector(basis: Basis, degree: usize, count: usize, knots: &[f32]) -> Option<Vec<f32>> {
+    match basis {
+        // Degrees come from the file, so sizes are checked for overflow.
+        Basis::BSpline if Some(knots.len()) == count.checked_add(degree).and_then(|n| n.checked_add(1)) => Some(knots.to_vec()),
+        Basis::Bezier if degree > 0 => {
+            let boundaries = if knots.is_empty() { vec![0.0, 1.0] } else { knots.to_vec() };
+            let patches = boundaries.len() - 1;
+            if patches == 0 || Some(count) != patches.checked_mul(degree).and_then(|n| n.checked_add(1)) {
+                return None;
+            }
+
+            let mut vector = vec![boundaries[0]; degree + 1];
+            for &b in &boundaries[1..patches] {
+                vector.resize(vector.len() + degree, b);
+            }
+            vector.resize(vector.len() + degree + 1, boundaries[patches]);
+            Some(vector)
+        },
+        _ => None
+    }
+}
+
+// Cox-de Boor recursion for the value of every basis function at `t`.
+fn basis_functions(knots: &[f32], degree: usize, count: usize, t: f32) -> Vec<f32> {
+    let last = knots[knots.len() - 1];
+    let mut n: Vec<f32> = (0..knots.len() - 1).map(|i| {
+        let in_span = knots[i] <= t && t < knots[i + 1];
+        // The end of the range belongs to the last non-empty span.
+        let at_end = t >= last && knots[i] < knots[i + 1] && knots[i + 1] == last;
+        if in_span || at_end { 1.0 } else { 0.0 }
+    }).collect();
+
+    for p in 1..=degree {
+        for i in 0..knots.len() - 1 - p {
+            let left = if knots[i + p] > knots[i] {
+                (t - knots[i]) / (knots[i + p] - knots[i]) * n[i]
+            } else {
+                0.0
+            };
+            let right = if knots[i + p + 1] > knots[i + 1] {
+                (knots[i + p + 1] - t) / (knots[i + p + 1] - knots[i + 1]) * n[i + 1]
+            } else {
+                0.0
+            };
+            n[i] = left + right;
+        }
+    }
+
+    n.truncate(count);
+    n
+}
+
+fn normalize(x: f32, y: f32, z: f32) -> Vector3 {
+    let length = (x * x + y * y + z * z).sqrt();
+    if length > 0.0 {
+        Vector3::new(x / length, y / length, z / length)
+    } else {
+        Vector3::zero()
+    }
+}
+
+// Whether (u, v) is inside `loops` by the even-odd rule. Each loop is closed back to its first point.
+fn inside(loops: &[Vec<(f32, f32)>], u: f32, v: f32) -> bool {
+    let mut inside = false;
+    for points in loops {
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            if (a.1 > v) != (b.1 > v) && u < a.0 + (v - a.1) / (b.1 - a.1) * (b.0 - a.0) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+impl ParameterCurve {
+    // Points along the curve from `start` to `end`, with `segments` steps between each pair of
+    // knots so corners are kept. `None` if the knots do not match the control points.
+    fn polyline(&self, start: f32, end: f32, segments: usize) -> Option<Vec<(f32, f32)>> {
+        let count = self.control_points.len();
+        let knots = knot_vector(self.basis, self.degree, count, &self.knots)?;
+        let (first, last) = (knots[0], knots[knots.len() - 1]);
+
+        let (low, high) = (start.min(end), start.max(end));
+        let mut breaks: Vec<f32> = knots.iter().cloned().filter(|&k| k > low && k < high).collect();
+        breaks.dedup();
+        if start > end {
+            breaks.reverse();
+        }
+        breaks.insert(0, start);
+        breaks.push(end);
+
+        let steps = breaks.windows(2)
+            .flat_map(|w| (0..segments).map(move |i| w[0] + (w[1] - w[0]) * i as f32 / segments as f32))
+            .chain(Some(end));
+        steps.map(|t| {
+            let n = basis_functions(&knots, self.degree, count, t.max(first).min(last));
+
+            let (mut u, mut v, mut w) = (0.0, 0.0, 0.0);
+            for (c, b) in self.control_points.iter().zip(&n) {
+                let weight = b * if self.rational { c.z } else { 1.0 };
+                u += c.x * weight;
+                v += c.y * weight;
+                w += weight;
+            }
+            if w != 0.0 { Some((u / w, v / w)) } else { None }
+        }).collect()
+    }
+}
+
+// Joins the curves of each loop into one polyline.
+fn trim_polylines(loops: &[Vec<TrimCurve>], segments: usize) -> Option<Vec<Vec<(f32, f32)>>> {
+    loops.iter().map(|curves| {
+        let mut points = Vec::new();
+        for c in curves {
+            points.extend(c.curve.polyline(c.start, c.end, segments)?);
+        }
+        Some(points)
+    }).collect()
+}
+
+impl Surface {
+    // Number of control points along `u` and `v`, or `None` if the knots do not match the control points.
+    fn control_grid(&self) -> Option<(usize, usize)> {
+        let count_u = match self.basis {
+            Basis::BSpline => self.knots_u.len().checked_sub(self.degree_u.checked_add(1)?)?,
+            Basis::Bezier => self.degree_u.checked_mul(self.knots_u.len().max(2) - 1)?.checked_add(1)?,
+        };
+        if count_u == 0 {
+            return None;
+        }
+        let count_v = self.control_points.len() / count_u;
+        if count_u * count_v != self.control_points.len() {
+            return None;
+        }
+        Some((count_u, count_v))
+    }
+
+    // Splits the surface into a grid of `resolution` by `resolution` quads, each made of two
+    // triangles. A triangle is kept if its centre is inside the trimming loops, if there are
+    // any, and outside the holes, so trimmed edges follow the grid. Trimming curves are
+    // evaluated with `resolution` segments per knot span. `None` if a curve cannot be evaluated.
+    pub(crate) fn tessellate(&self, resolution: usize) -> Option<(Vec<SurfacePoint>, Vec<[usize; 3]>)> {
+        let resolution = resolution.max(1);
+        // The resolution comes from the caller, so sizes are checked for overflow.
+        let row = resolution.checked_add(1)?;
+        let point_count = row.checked_mul(row)?;
+        let triangle_count = resolution.checked_mul(resolution)?.checked_mul(2)?;
+        let (count_u, count_v) = self.control_grid()?;
+        let knots_u = knot_vector(self.basis, self.degree_u, count_u, &self.knots_u)?;
+        let knots_v = knot_vector(self.basis, self.degree_v, count_v, &self.knots_v)?;
+        let trims = trim_polylines(&self.trims, resolution)?;
+        let holes = trim_polylines(&self.holes, resolution)?;
+
+        let mut grid = Vec::with_capacity(point_count);
+        for j in 0..=resolution {
+            let fv = j as f32 / resolution as f32;
+            let nv = basis_functions(&knots_v, self.degree_v, count_v, self.start_v + (self.end_v - self.start_v) * fv);
+            for i in 0..=resolution {
+                let fu = i as f32 / resolution as f32;
+                let nu = basis_functions(&knots_u, self.degree_u, count_u, self.start_u + (self.end_u - self.start_u) * fu);
+
+                let (mut x, mut y, mut z, mut w) = (0.0, 0.0, 0.0, 0.0);
+                for (b, bv) in nv.iter().enumerate() {
+                    for (a, au) in nu.iter().enumerate() {
+                        let c = &self.control_points[b * count_u + a];
+                        let weight = au * bv * if self.rational { c.w } else { 1.0 };
+                        x += c.p.x * weight;
+                        y += c.p.y * weight;
+                        z += c.p.z * weight;
+                        w += weight;
+                    }
+                }
+                if w != 0.0 {
+                    x /= w;
+                    y /= w;
+                    z /= w;
+                }
+                grid.push((Vector3::new(x, y, z), Vector3::new(fu, fv, 0.0)));
+            }
+        }
+
+        // Normals from the differences between neighbouring grid points.
+        let mut points = Vec::with_capacity(grid.len());
+        for j in 0..=resolution {
+            for i in 0..=resolution {
+                let (u0, u1) = (&grid[j * row + i.saturating_sub(1)].0, &grid[j * row + (i + 1).min(resolution)].0);
+                let (v0, v1) = (&grid[j.saturating_sub(1) * row + i].0, &grid[(j + 1).min(resolution) * row + i].0);
+                let du = (u1.x - u0.x, u1.y - u0.y, u1.z - u0.z);
+                let dv = (v1.x - v0.x, v1.y - v0.y, v1.z - v0.z);
+                let (p, uv) = grid[j * row + i];
+                points.push(SurfacePoint {
+                    p,
+                    normal: normalize(du.1 * dv.2 - du.2 * dv.1, du.2 * dv.0 - du.0 * dv.2, du.0 * dv.1 - du.1 * dv.0),
+                    uv,
+                });
+            }
+        }
+
+        let mut triangles = Vec::with_capacity(triangle_count);
+        for j in 0..resolution {
+            for i in 0..resolution {
+                let a = j * row + i;
+                triangles.push([a, a + 1, a + row + 1]);
+                triangles.push([a, a + row + 1, a + row]);
+            }
+        }
+
+        if trims.is_empty() && holes.is_empty() {
+            return Some((points, triangles));
+        }
+
+        triangles.retain(|t| {
+            let fu = t.iter().map(|&k| points[k].uv.x).sum::<f32>() / 3.0;
+            let fv = t.iter().map(|&k| points[k].uv.y).sum::<f32>() / 3.0;
+            let u = self.start_u + (self.end_u - self.start_u) * fu;
+            let v = self.start_v + (self.end_v - self.start_v) * fv;
+            (trims.is_empty() || inside(&trims, u, v)) && !inside(&holes, u, v)
+        });
+
+        // Drops the points no remaining triangle uses.
+        let mut used = vec![false; points.len()];
+        for &k in triangles.iter().flatten() {
+            used[k] = true;
+        }
+        let mut remap = vec![0; points.len()];
+        let mut kept = Vec::new();
+        for (k, p) in points.into_iter().enumerate() {
+            if used[k] {
+                remap[k] = kept.len();
+                kept.push(p);
+            }
+        }
+        for k in triangles.iter_mut().flatten() {
+            *k = remap[*k];
+        }
+
+        Some((kept, triangles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_point(x: f32, y: f32, z: f32) -> ControlPoint {
+        ControlPoint { p: Vector3::new(x, y, z), w: 1.0 }
+    }
+
+    // A bilinear patch over the unit square, raised at one corner.
+    fn patch(basis: Basis, knots: Vec<f32>) -> Surface {
+        Surface {
+            basis,
+            rational: false,
+            degree_u: 1,
+            degree_v: 1,
+            start_u: 0.0,
+            end_u: 1.0,
+            start_v: 0.0,
+            end_v: 1.0,
+            control_points: vec![
+                control_point(0.0, 0.0, 0.0), control_point(1.0, 0.0, 0.0),
+                control_point(0.0, 1.0, 0.0), control_point(1.0, 1.0, 1.0),
+            ],
+            knots_u: knots.clone(),
+            knots_v: knots,
+            trims: Vec::new(),
+            holes: Vec::new(),
+            material: None,
+        }
+    }
+
+    #[test]
+    fn test_basis_functions_partition_of_unity() {
+        let knots = [0.0, 0.0, 0.0, 0.5, 1.0, 1.0, 1.0];
+        for &t in &[0.0, 0.25, 0.5, 0.75, 1.0] {
+            let n = basis_functions(&knots, 2, 4, t);
+            assert!((n.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_bezier_knot_vector() {
+        assert_eq!(knot_vector(Basis::Bezier, 3, 7, &[0.0, 1.0, 2.0]),
+                   Some(vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0]));
+        assert_eq!(knot_vector(Basis::Bezier, 3, 6, &[0.0, 1.0, 2.0]), None);
+    }
+
+    #[test]
+    fn test_tessellate_bspline() {
+        let (points, triangles) = patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]).tessellate(2).unwrap();
+
+        assert_eq!(points.len(), 9);
+        assert_eq!(triangles.len(), 8);
+        assert_eq!((points[4].p.x, points[4].p.y, points[4].p.z), (0.5, 0.5, 0.25));
+        assert_eq!((points[8].p.x, points[8].p.y, points[8].p.z), (1.0, 1.0, 1.0));
+        assert_eq!((points[0].normal.x, points[0].normal.y, points[0].normal.z), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_tessellate_bezier_matches_bspline() {
+        let bezier = patch(Basis::Bezier, vec![0.0, 1.0]).tessellate(3).unwrap().0;
+        let bspline = patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]).tessellate(3).unwrap().0;
+
+        for (a, b) in bezier.iter().zip(&bspline) {
+            assert_eq!((a.p.x, a.p.y, a.p.z), (b.p.x, b.p.y, b.p.z));
+        }
+    }
+
+    #[test]
+    fn test_tessellate_huge_degree() {
+        let mut surface = patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]);
+        surface.degree_u = usize::MAX;
+        assert!(surface.tessellate(2).is_none());
+
+        let mut surface = patch(Basis::Bezier, vec![0.0, 1.0]);
+        surface.degree_u = usize::MAX;
+        surface.degree_v = usize::MAX;
+        assert!(surface.tessellate(2).is_none());
+    }
+
+    // A closed polyline through `points`, as a linear B-spline.
+    fn trim_loop(points: &[(f32, f32)]) -> Vec<TrimCurve> {
+        let n = points.len();
+        let mut knots = vec![0.0];
+        knots.extend((0..n).map(|i| i as f32));
+        knots.push((n - 1) as f32);
+        vec![TrimCurve {
+            start: 0.0,
+            end: (n - 1) as f32,
+            curve: ParameterCurve {
+                basis: Basis::BSpline,
+                rational: false,
+                degree: 1,
+                control_points: points.iter().map(|&(u, v)| Vector3::new(u, v, 1.0)).collect(),
+                knots,
+            },
+        }]
+    }
+
+    #[test]
+    fn test_tessellate_trimmed() {
+        let mut surface = patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]);
+        surface.trims.push(trim_loop(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)]));
+        let (points, triangles) = surface.tessellate(2).unwrap();
+
+        // Only the triangles below the diagonal remain, and the three points above it are dropped.
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(points.len(), 6);
+        assert!(points.iter().all(|p| p.uv.x >= p.uv.y));
+        assert!(triangles.iter().flatten().all(|&k| k < points.len()));
+    }
+
+    #[test]
+    fn test_tessellate_hole() {
+        let mut surface = patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]);
+        surface.holes.push(trim_loop(&[(0.25, 0.25), (0.75, 0.25), (0.75, 0.75), (0.25, 0.75), (0.25, 0.25)]));
+        let (points, triangles) = surface.tessellate(4).unwrap();
+
+        // The four quads around the centre are cut out, along with the centre point.
+        assert_eq!(triangles.len(), 24);
+        assert_eq!(points.len(), 24);
+        assert!(points.iter().all(|p| (p.uv.x, p.uv.y) != (0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_tessellate_invalid_trim() {
+        let mut surface = patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]);
+        let mut curves = trim_loop(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        curves[0].curve.knots.pop();
+        surface.trims.push(curves);
+        assert!(surface.tessellate(2).is_none());
+    }
+
+    #[test]
+    fn test_tessellate_huge_resolution() {
+        assert!(patch(Basis::BSpline, vec![0.0, 0.0, 1.0, 1.0]).tessellate(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn test_tessellate_mismatched_knots() {
+        assert!(patch(Basis::BSpline, vec![0.0, 1.0]).tessellate(2).is_none());
+    }
+}
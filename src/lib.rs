@@ -1,11 +1,26 @@
 extern crate nom;
 extern crate tdmath;
 
+mod loader;
+mod mtl;
 mod obj;
+mod parse;
+mod resolver;
+mod triangulate;
+pub mod freeform;
+pub mod material;
 pub mod model;
 
-pub use self::model::{Model, Vertex};
-pub use self::obj::parse_obj_file;
+pub use self::freeform::{Basis, ControlPoint, Curve, ParameterCurve, Surface, TrimCurve};
+pub use self::material::{ImageChannel, Material, TextureMap};
+pub use self::model::{Group, Model, Scene, Submesh, UnknownStatement, Vertex};
+pub use self::loader::{load_obj, load_obj_sandboxed, load_obj_with_options, load_obj_with_resolver, LoadError};
+pub use self::mtl::{parse_mtl_file, parse_mtl_file_with_options, MtlError, MtlOptions};
+pub use self::obj::{parse_obj_file, parse_obj_file_with_options, parse_obj_scene, parse_obj_scene_with_options};
+pub use self::obj::{IndexAttribute, ObjError, ObjOptions};
+pub use self::parse::Location;
+pub use self::resolver::{FileSystemResolver, MemoryResolver, ResourceResolver, Sandbox, SandboxViolation};
+pub use self::triangulate::Triangulation;
 
 #[cfg(test)]
 mod tests {
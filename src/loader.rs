@@ -0,0 +1,395 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use crate::model::Model;
+use crate::mtl::{parse_mtl_file_with_options, MtlError, MtlOptions};
+use crate::obj::{parse_obj_file_with_options, ObjError, ObjOptions};
+use crate::resolver::{FileSystemResolver, ResourceResolver, Sandbox, SandboxViolation};
+
+/// A failure while loading an OBJ file and the material libraries it references.
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file could not be read.
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Obj {
+        path: PathBuf,
+        error: ObjError,
+    },
+    Mtl {
+        path: PathBuf,
+        error: MtlError,
+    },
+    /// A `mtllib` statement named a file that does not exist.
+    MissingLibrary {
+        library: String,
+        path: PathBuf,
+    },
+    /// A `mtllib` or texture reference was refused by the sandbox.
+    Refused {
+        reference: String,
+        path: PathBuf,
+        violation: SandboxViolation,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io { ref path, ref error } => write!(f, "Unable to read {}: {}", path.display(), error),
+            LoadError::Obj { ref path, ref error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::Mtl { ref path, ref error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::MissingLibrary { ref library, ref path } => {
+                write!(f, "Material library `{}` not found at {}", library, path.display())
+            },
+            LoadError::Refused { ref reference, violation: SandboxViolation::AbsolutePath, .. } => {
+                write!(f, "Refused to load `{}`: absolute paths are not allowed", reference)
+            },
+            LoadError::Refused { ref reference, ref path, violation: SandboxViolation::OutsideRoot } => {
+                write!(f, "Refused to load `{}`: {} is outside the sandbox", reference, path.display())
+            },
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Io { ref error, .. } => Some(error),
+            LoadError::Obj { ref error, .. } => Some(error),
+            LoadError::Mtl { ref error, .. } => Some(error),
+            LoadError::MissingLibrary { .. } |
+            LoadError::Refused { .. } => None,
+        }
+    }
+}
+
+fn read_resource<R: ResourceResolver + ?Sized>(resolver: &R, path: &Path) -> io::Result<String> {
+    let mut data = String::new();
+    resolver.open(path)?.read_to_string(&mut data)?;
+    Ok(data)
+}
+
+/// Reads an OBJ file along with the material libraries named by its `mtllib` statements,
+/// which are found relative to the OBJ file's directory.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Model, LoadError> {
+    load_obj_with_options(path, &ObjOptions::default())
+}
+
+pub fn load_obj_with_options<P: AsRef<Path>>(path: P, options: &ObjOptions) -> Result<Model, LoadError> {
+    load_obj_with_resolver(path, &FileSystemResolver, options)
+}
+
+// Resolves a reference from the file at `base`, checking it against the sandbox if there is one.
+fn resolve<R: ResourceResolver + ?Sized>(resolver: &R, sandbox: Option<&Sandbox>, base: &Path, name: &str) -> Result<PathBuf, LoadError> {
+    let path = resolver.resolve(base, name);
+    if let Some(sandbox) = sandbox {
+        let mut result = sandbox.check(name, &path);
+        // A symbolic link inside the root may point anywhere.
+        if let (Ok(()), Some(real_path)) = (result, resolver.canonicalize(&path)) {
+            result = match resolver.canonicalize(sandbox.root()) {
+                Some(ref root) if real_path.starts_with(root) => Ok(()),
+                _ => Err(SandboxViolation::OutsideRoot)
+            };
+        }
+        if let Err(violation) = result {
+            return Err(LoadError::Refused {
+                reference: name.to_string(),
+                path,
+                violation,
+            });
+        }
+    }
+    Ok(path)
+}
+
+// Reads the material library `name` referenced from `base`. The contents are `None` if it does not exist.
+fn read_library<R: ResourceResolver + ?Sized>(resolver: &R, sandbox: Option<&Sandbox>, base: &Path, name: &str) -> Result<(PathBuf, Option<String>), LoadError> {
+    let path = resolve(resolver, sandbox, base, name)?;
+    match read_resource(resolver, &path) {
+        Ok(data) => Ok((path, Some(data))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok((path, None)),
+        Err(error) => Err(LoadError::Io { path, error })
+    }
+}
+
+/// Like `load_obj`, but reads the OBJ file, its material libraries and resolves texture paths through `resolver`.
+pub fn load_obj_with_resolver<P, R>(path: P, resolver: &R, options: &ObjOptions) -> Result<Model, LoadError>
+    where P: AsRef<Path>, R: ResourceResolver + ?Sized
+{
+    load(path.as_ref(), resolver, None, options)
+}
+
+/// Like `load_obj_with_resolver`, but for untrusted files: every `mtllib` and texture
+/// reference must be a relative path that stays inside the sandbox root.
+pub fn load_obj_sandboxed<P, R>(path: P, resolver: &R, sandbox: &Sandbox, options: &ObjOptions) -> Result<Model, LoadError>
+    where P: AsRef<Path>, R: ResourceResolver + ?Sized
+{
+    load(path.as_ref(), resolver, Some(sandbox), options)
+}
+
+fn load<R: ResourceResolver + ?Sized>(path: &Path, resolver: &R, sandbox: Option<&Sandbox>, options: &ObjOptions) -> Result<Model, LoadError> {
+    let data = read_resource(resolver, path).map_err(|error| LoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let mut model = parse_obj_file_with_options(&data, options).map_err(|error| LoadError::Obj {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    let mut materials = Vec::new();
+    for argument in &model.material_libraries {
+        // `mtllib` lists file names separated by spaces, but some exporters write a
+        // single file name containing spaces. The whole argument wins if it exists.
+        let mut libraries = Vec::new();
+        if argument.contains(char::is_whitespace) {
+            if let (library_path, Some(data)) = read_library(resolver, sandbox, path, argument)? {
+                libraries.push((library_path, data));
+            }
+        }
+        if libraries.is_empty() {
+            for library in argument.split_whitespace() {
+                match read_library(resolver, sandbox, path, library)? {
+                    (library_path, Some(data)) => libraries.push((library_path, data)),
+                    (library_path, None) => {
+                        return Err(LoadError::MissingLibrary {
+                            library: library.to_string(),
+                            path: library_path,
+                        });
+                    }
+                }
+            }
+        }
+
+        let mtl_options = MtlOptions {
+            strict: options.strict,
+        };
+        for (library_path, data) in libraries {
+            let mut library_materials = parse_mtl_file_with_options(&data, &mtl_options).map_err(|error| LoadError::Mtl {
+                path: library_path.clone(),
+                error,
+            })?;
+            for material in &mut library_materials {
+                for map in material.texture_maps_mut() {
+                    map.resolved_path = Some(resolve(resolver, sandbox, &library_path, &map.path)?);
+                }
+            }
+            materials.extend(library_materials);
+        }
+    }
+    model.link_materials(materials);
+
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::MemoryResolver;
+
+    fn sandboxed_resolver(obj: &str, mtl: &str) -> MemoryResolver {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("uploads/42/model.obj", obj);
+        resolver.insert("uploads/42/model.mtl", mtl);
+        resolver.insert("uploads/secret.mtl", "newmtl Secret\n");
+        resolver
+    }
+
+    fn asset(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(name)
+    }
+
+    #[test]
+    fn test_load_obj() {
+        let model = load_obj(asset("cube_uv.obj")).unwrap();
+
+        assert_eq!(model.triangles.len(), 12 * 3);
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.submeshes[0].material_index, Some(0));
+        assert_eq!(model.material("Material").unwrap().illumination_model, Some(2));
+    }
+
+    #[test]
+    fn test_load_obj_missing_library() {
+        match load_obj(asset("missing_library.obj")) {
+            Err(LoadError::MissingLibrary { library, path }) => {
+                assert_eq!(library, "missing.mtl");
+                assert_eq!(path, asset("missing.mtl"));
+            },
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("missing library was not reported")
+        }
+    }
+
+    #[test]
+    fn test_load_obj_missing_file() {
+        match load_obj(asset("does_not_exist.obj")) {
+            Err(LoadError::Io { path, error }) => {
+                assert_eq!(path, asset("does_not_exist.obj"));
+                assert_eq!(error.kind(), io::ErrorKind::NotFound);
+            },
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("missing file was not reported")
+        }
+    }
+
+    #[test]
+    fn test_load_obj_with_memory_resolver() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("models/tri.obj", "mtllib common.mtl materials/red.mtl\n\
+                                           v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                                           usemtl Red\nf 1 2 3\nusemtl Wood\nf 1 2 3\n");
+        resolver.insert("models/common.mtl", "newmtl Wood\nmap_Kd textures/wood.png\n");
+        resolver.insert("models/materials/red.mtl", "newmtl Red\nKd 1 0 0\nmap_Kd red.png\n");
+
+        let model = load_obj_with_resolver("models/tri.obj", &resolver, &ObjOptions::default()).unwrap();
+
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(model.submeshes[0].material_index, Some(1));
+        assert_eq!(model.submeshes[1].material_index, Some(0));
+
+        let wood = model.material("Wood").unwrap().diffuse_map.as_ref().unwrap();
+        assert_eq!(wood.path, "textures/wood.png");
+        assert_eq!(wood.resolved_path, Some(PathBuf::from("models/textures/wood.png")));
+        let red = model.material("Red").unwrap().diffuse_map.as_ref().unwrap();
+        assert_eq!(red.resolved_path, Some(PathBuf::from("models/materials/red.png")));
+    }
+
+    #[test]
+    fn test_load_obj_library_names_with_spaces() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("tri.obj", "mtllib my model.mtl\nmtllib a.mtl b.mtl\n\
+                                    v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Base Coat\nf 1 2 3\n");
+        resolver.insert("my model.mtl", "newmtl Base Coat\nmap_Kd textures/base coat.png\n");
+        resolver.insert("a.mtl", "newmtl A\n");
+        resolver.insert("b.mtl", "newmtl B\n");
+
+        let model = load_obj_with_resolver("tri.obj", &resolver, &ObjOptions::default()).unwrap();
+
+        let names: Vec<&str> = model.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["Base Coat", "A", "B"]);
+        assert_eq!(model.submeshes[0].material_index, Some(0));
+        assert_eq!(model.materials[0].diffuse_map.as_ref().unwrap().path, "textures/base coat.png");
+    }
+
+    #[test]
+    fn test_load_obj_missing_one_of_several_libraries() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("tri.obj", "mtllib a.mtl b.mtl\n");
+        resolver.insert("a.mtl", "newmtl A\n");
+
+        match load_obj_with_resolver("tri.obj", &resolver, &ObjOptions::default()) {
+            Err(LoadError::MissingLibrary { library, path }) => {
+                assert_eq!(library, "b.mtl");
+                assert_eq!(path, PathBuf::from("b.mtl"));
+            },
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("missing library was not reported")
+        }
+    }
+
+    #[test]
+    fn test_load_obj_unknown_material_statements() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("tri.obj", "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl A\nf 1 2 3\n");
+        resolver.insert("a.mtl", "newmtl A\nKm 0.2\nKd 1 0 0\n");
+
+        let model = load_obj_with_resolver("tri.obj", &resolver, &ObjOptions::default()).unwrap();
+        assert_eq!(model.material("A").unwrap().diffuse.unwrap().x, 1.0);
+
+        let options = ObjOptions {
+            strict: true,
+            ..ObjOptions::default()
+        };
+        match load_obj_with_resolver("tri.obj", &resolver, &options) {
+            Err(LoadError::Mtl { error: MtlError::UnknownStatement(location), .. }) => assert_eq!(location.line, 2),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unknown statement was not reported")
+        }
+    }
+
+    #[test]
+    fn test_load_obj_sandboxed() {
+        let resolver = sandboxed_resolver("mtllib model.mtl\n", "newmtl Wood\nmap_Kd textures/./wood.png\n");
+        let sandbox = Sandbox::new("uploads/42");
+
+        let model = load_obj_sandboxed("uploads/42/model.obj", &resolver, &sandbox, &ObjOptions::default()).unwrap();
+
+        let wood = model.material("Wood").unwrap().diffuse_map.as_ref().unwrap();
+        assert_eq!(wood.resolved_path, Some(PathBuf::from("uploads/42/textures/./wood.png")));
+    }
+
+    #[test]
+    fn test_load_obj_sandboxed_absolute_path() {
+        let root = std::env::current_dir().unwrap().join("uploads/42");
+        let mut resolver = MemoryResolver::new();
+        resolver.insert(root.join("model.obj"), "mtllib model.mtl\n");
+        resolver.insert(root.join("model.mtl"), "newmtl Wood\n");
+        let sandbox = Sandbox::new("uploads/42");
+
+        let model = load_obj_sandboxed(root.join("model.obj"), &resolver, &sandbox, &ObjOptions::default()).unwrap();
+        assert!(model.material("Wood").is_some());
+    }
+
+    #[test]
+    fn test_load_obj_sandboxed_escape() {
+        let resolver = sandboxed_resolver("mtllib ../secret.mtl\n", "");
+        let sandbox = Sandbox::new("uploads/42");
+
+        match load_obj_sandboxed("uploads/42/model.obj", &resolver, &sandbox, &ObjOptions::default()) {
+            Err(LoadError::Refused { reference, violation, .. }) => {
+                assert_eq!(reference, "../secret.mtl");
+                assert_eq!(violation, SandboxViolation::OutsideRoot);
+            },
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("escaping reference was not refused")
+        }
+
+        // Without a sandbox the same file loads.
+        assert!(load_obj_with_resolver("uploads/42/model.obj", &resolver, &ObjOptions::default()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_obj_sandboxed_symlink() {
+        let dir = std::env::temp_dir().join(format!("modelloader-symlink-{}", std::process::id()));
+        let root = dir.join("upload");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(dir.join("secret.mtl"), "newmtl Secret\n").unwrap();
+        std::fs::write(root.join("model.obj"), "mtllib model.mtl\n").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.mtl"), root.join("model.mtl")).unwrap();
+
+        let sandbox = Sandbox::new(&root);
+        let result = load_obj_sandboxed(root.join("model.obj"), &FileSystemResolver, &sandbox, &ObjOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        match result {
+            Err(LoadError::Refused { reference, violation, .. }) => {
+                assert_eq!(reference, "model.mtl");
+                assert_eq!(violation, SandboxViolation::OutsideRoot);
+            },
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("symbolic link out of the sandbox was followed")
+        }
+    }
+
+    #[test]
+    fn test_load_obj_sandboxed_absolute_texture() {
+        let resolver = sandboxed_resolver("mtllib model.mtl\n", "newmtl Wood\nmap_Kd /etc/passwd\n");
+        let sandbox = Sandbox::new("uploads/42");
+
+        match load_obj_sandboxed("uploads/42/model.obj", &resolver, &sandbox, &ObjOptions::default()) {
+            Err(LoadError::Refused { reference, violation, .. }) => {
+                assert_eq!(reference, "/etc/passwd");
+                assert_eq!(violation, SandboxViolation::AbsolutePath);
+            },
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("absolute reference was not refused")
+        }
+    }
+}
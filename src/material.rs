@@ -0,0 +1,213 @@
+extern crate tdmath;
+
+use std::path::PathBuf;
+use tdmath::Vector3;
+
+/// The image channel a scalar texture reads from, set with `-imfchan`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageChannel {
+    Red,
+    Green,
+    Blue,
+    Matte,
+    Luminance,
+    Depth,
+}
+
+/// A texture reference from a `map_*`, `bump`, `disp`, `decal` or `refl` statement.
+#[derive(Debug, Clone)]
+pub struct TextureMap {
+    /// The file name as written in the MTL file.
+    pub path: String,
+    /// Where the texture was found, set when the material is read by a loader.
+    pub resolved_path: Option<PathBuf>,
+    /// `-o`, offset of the texture origin.
+    pub offset: Vector3,
+    /// `-s`, scale of the texture pattern.
+    pub scale: Vector3,
+    /// `-t`, turbulence.
+    pub turbulence: Vector3,
+    /// `-bm`, multiplier for bump map values.
+    pub bump_multiplier: Option<f32>,
+    /// `-clamp`, clamp texture coordinates to 0..1 instead of repeating.
+    pub clamp: bool,
+    /// `-blendu`, horizontal texture blending.
+    pub blend_u: bool,
+    /// `-blendv`, vertical texture blending.
+    pub blend_v: bool,
+    /// `-cc`, color correction.
+    pub color_correction: bool,
+    /// `-boost`, sharpening of mip-mapped textures.
+    pub boost: Option<f32>,
+    /// `-mm`, the base value added to texture values.
+    pub base: f32,
+    /// `-mm`, the gain applied to texture values.
+    pub gain: f32,
+    /// `-imfchan`
+    pub channel: Option<ImageChannel>,
+    /// `-texres`
+    pub resolution: Option<u32>,
+    /// `-type`, the reflection map projection, e.g. `sphere` or `cube_top`.
+    pub reflection_type: Option<String>,
+}
+
+impl TextureMap {
+    pub fn new(path: &str) -> TextureMap {
+        TextureMap {
+            path: path.to_string(),
+            resolved_path: None,
+            offset: Vector3::new(0.0, 0.0, 0.0),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            turbulence: Vector3::new(0.0, 0.0, 0.0),
+            bump_multiplier: None,
+            clamp: false,
+            blend_u: true,
+            blend_v: true,
+            color_correction: false,
+            boost: None,
+            base: 0.0,
+            gain: 1.0,
+            channel: None,
+            resolution: None,
+            reflection_type: None,
+        }
+    }
+}
+
+/// A material from an MTL file. Properties the file does not set are `None`.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    /// `Ka`. This and the other colors may be given as `xyz`, converted to RGB. `spectral`
+    /// curves are not read and leave the color `None`.
+    pub ambient: Option<Vector3>,
+    /// `Kd`
+    pub diffuse: Option<Vector3>,
+    /// `Ks`
+    pub specular: Option<Vector3>,
+    /// `Ke`
+    pub emissive: Option<Vector3>,
+    /// `Tf`
+    pub transmission_filter: Option<Vector3>,
+    /// `Ns`, the specular exponent.
+    pub shininess: Option<f32>,
+    /// `Ni`, the index of refraction.
+    pub optical_density: Option<f32>,
+    /// `d`, or `1 - Tr`. 1.0 is fully opaque.
+    pub dissolve: Option<f32>,
+    /// Set by `d -halo`: dissolve depends on the surface orientation.
+    pub dissolve_halo: bool,
+    /// `sharpness` of reflections.
+    pub sharpness: Option<f32>,
+    /// `illum`, from 0 to 10.
+    pub illumination_model: Option<u8>,
+    /// `map_Ka`
+    pub ambient_map: Option<TextureMap>,
+    /// `map_Kd`
+    pub diffuse_map: Option<TextureMap>,
+    /// `map_Ks`
+    pub specular_map: Option<TextureMap>,
+    /// `map_Ke`
+    pub emissive_map: Option<TextureMap>,
+    /// `map_Ns`
+    pub shininess_map: Option<TextureMap>,
+    /// `map_d`
+    pub dissolve_map: Option<TextureMap>,
+    /// `map_bump`, `map_Bump` or `bump`
+    pub bump_map: Option<TextureMap>,
+    /// `disp`
+    pub displacement_map: Option<TextureMap>,
+    /// `decal`
+    pub decal_map: Option<TextureMap>,
+    /// `refl`. Cube maps use one statement per face, told apart by `reflection_type`.
+    pub reflection_maps: Vec<TextureMap>,
+    /// `Pr`, PBR roughness.
+    pub roughness: Option<f32>,
+    /// `Pm`, PBR metallic.
+    pub metallic: Option<f32>,
+    /// `Ps`, PBR sheen.
+    pub sheen: Option<f32>,
+    /// `Pc`, PBR clearcoat thickness.
+    pub clearcoat_thickness: Option<f32>,
+    /// `Pcr`, PBR clearcoat roughness.
+    pub clearcoat_roughness: Option<f32>,
+    /// `aniso`, PBR anisotropy.
+    pub anisotropy: Option<f32>,
+    /// `anisor`, PBR anisotropy rotation.
+    pub anisotropy_rotation: Option<f32>,
+    /// `map_Pr`
+    pub roughness_map: Option<TextureMap>,
+    /// `map_Pm`
+    pub metallic_map: Option<TextureMap>,
+    /// `map_Ps`
+    pub sheen_map: Option<TextureMap>,
+    /// `norm`, a tangent space normal map.
+    pub normal_map: Option<TextureMap>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: name.to_string(),
+            ambient: None,
+            diffuse: None,
+            specular: None,
+            emissive: None,
+            transmission_filter: None,
+            shininess: None,
+            optical_density: None,
+            dissolve: None,
+            dissolve_halo: false,
+            sharpness: None,
+            illumination_model: None,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            shininess_map: None,
+            dissolve_map: None,
+            bump_map: None,
+            displacement_map: None,
+            decal_map: None,
+            reflection_maps: Vec::new(),
+            roughness: None,
+            metallic: None,
+            sheen: None,
+            clearcoat_thickness: None,
+            clearcoat_roughness: None,
+            anisotropy: None,
+            anisotropy_rotation: None,
+            roughness_map: None,
+            metallic_map: None,
+            sheen_map: None,
+            normal_map: None,
+        }
+    }
+
+    /// Every texture map the material uses.
+    pub fn texture_maps(&self) -> Vec<&TextureMap> {
+        let maps = [
+            &self.ambient_map, &self.diffuse_map, &self.specular_map, &self.emissive_map,
+            &self.shininess_map, &self.dissolve_map, &self.bump_map, &self.displacement_map,
+            &self.decal_map, &self.roughness_map, &self.metallic_map, &self.sheen_map,
+            &self.normal_map,
+        ];
+        maps.iter()
+            .filter_map(|m| m.as_ref())
+            .chain(self.reflection_maps.iter())
+            .collect()
+    }
+
+    pub(crate) fn texture_maps_mut(&mut self) -> Vec<&mut TextureMap> {
+        let maps = vec![
+            &mut self.ambient_map, &mut self.diffuse_map, &mut self.specular_map, &mut self.emissive_map,
+            &mut self.shininess_map, &mut self.dissolve_map, &mut self.bump_map, &mut self.displacement_map,
+            &mut self.decal_map, &mut self.roughness_map, &mut self.metallic_map, &mut self.sheen_map,
+            &mut self.normal_map,
+        ];
+        maps.into_iter()
+            .filter_map(|m| m.as_mut())
+            .chain(self.reflection_maps.iter_mut())
+            .collect()
+    }
+}
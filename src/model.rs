@@ -1,16 +1,123 @@
 extern crate tdmath;
 
+use std::fmt;
+use std::ops::Range;
 use tdmath::Vector3;
+use crate::freeform::{Curve, Surface};
+use crate::material::Material;
 
 #[derive(Debug)]
 pub struct Vertex {
     pub p: Vector3,
+    /// Weight of the position, used by rational curves and surfaces. 1.0 unless the `v` statement sets it.
+    pub w: f32,
+    /// Texture coordinates. `z` is the optional third `vt` component, for 3D textures.
     pub uv: Vector3,
     pub normal: Vector3,
+    /// RGB color from the `v` statement, `None` if the file gave no color.
+    pub color: Option<Vector3>,
+}
+
+/// A named set of faces from a `g` statement. Faces before any `g` are in the group `default`.
+#[derive(Debug)]
+pub struct Group {
+    pub name: String,
+    /// Ranges of `Model::triangles` belonging to the group.
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl Group {
+    pub(crate) fn add_triangles(&mut self, range: Range<usize>) {
+        if let Some(last) = self.ranges.last_mut() {
+            if last.end == range.start {
+                last.end = range.end;
+                return;
+            }
+        }
+        self.ranges.push(range);
+    }
+}
+
+/// A run of triangles drawn with the same material, started by a `usemtl` statement.
+#[derive(Debug)]
+pub struct Submesh {
+    /// `None` for faces that appear before any `usemtl`.
+    pub material: Option<String>,
+    /// Index into `Model::materials`, once the material libraries have been loaded.
+    pub material_index: Option<usize>,
+    /// Range of `Model::triangles` drawn with the material.
+    pub range: Range<usize>,
+}
+
+/// A statement the parser did not recognize, such as a vendor extension.
+/// Kept when `ObjOptions::strict` is off.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownStatement {
+    /// 1-based line number of the statement.
+    pub line: usize,
+    pub keyword: String,
+    /// The rest of the statement as written, without any comment.
+    pub arguments: String,
+}
+
+impl fmt::Display for UnknownStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: unrecognized statement `{}`", self.line, self.keyword)
+    }
 }
 
 pub struct Model {
     pub name: String,
     pub vertices: Vec<Vertex>,
     pub triangles: Vec<usize>,
-}
\ No newline at end of file
+    /// The smoothing group set by `s` for each triangle, one entry per three `triangles`
+    /// indexes. 0 for triangles outside any smoothing group.
+    pub smoothing_groups: Vec<u32>,
+    /// Segments of the polylines from `l` statements, as pairs of indexes into `vertices`.
+    pub lines: Vec<[usize; 2]>,
+    /// Indexes into `vertices` from `p` statements.
+    pub points: Vec<usize>,
+    pub groups: Vec<Group>,
+    pub submeshes: Vec<Submesh>,
+    /// The argument of each `mtllib` statement, in the order they appear. An argument names
+    /// either one file, possibly with spaces in its name, or several files separated by spaces.
+    pub material_libraries: Vec<String>,
+    /// Materials read from `material_libraries`. Empty unless the model was loaded with `load_obj`.
+    pub materials: Vec<Material>,
+    /// Free-form curves from `curv` statements.
+    pub curves: Vec<Curve>,
+    /// Free-form surfaces from `surf` statements. With `ObjOptions::tessellation` set they
+    /// are also added to `triangles`.
+    pub surfaces: Vec<Surface>,
+    /// Statements that were skipped because they were not recognized. Models in a `Scene`
+    /// leave this empty, as the scene keeps the statements of the whole file.
+    pub unknown_statements: Vec<UnknownStatement>,
+}
+
+impl Model {
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    pub fn material(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|m| m.name == name)
+    }
+
+    // Stores the materials and points each submesh at the one it names.
+    // If several libraries define the same name, the first definition wins.
+    pub(crate) fn link_materials(&mut self, materials: Vec<Material>) {
+        self.materials = materials;
+        for submesh in &mut self.submeshes {
+            submesh.material_index = match submesh.material {
+                Some(ref name) => self.materials.iter().position(|m| &m.name == name),
+                None => None
+            };
+        }
+    }
+}
+
+pub struct Scene {
+    pub models: Vec<Model>,
+    /// Statements that were skipped because they were not recognized.
+    pub unknown_statements: Vec<UnknownStatement>,
+}
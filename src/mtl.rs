@@ -0,0 +1,837 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use nom::*;
+use nom::types::CompleteStr;
+use tdmath::Vector3;
+use crate::material::{ImageChannel, Material, TextureMap};
+use crate::parse::{error_location, ignore_lines, keyword, line_end, name, only_ignored_lines, spaces, statement_start, unknown_statement, word, Location};
+
+/*
+    Material Name
+*/
+
+named!(material_name<CompleteStr, Cow<str>>,
+    do_parse!(
+        spaces >>
+        n: name >>
+        line_end >>
+
+        (n)
+    )
+);
+
+/*
+    Colors
+*/
+
+// `r g b`, or a single value used for all three channels.
+named!(components<CompleteStr, Vector3>,
+    do_parse!(
+        r: float >>
+        gb: opt!(
+            do_parse!(
+                spaces >>
+                g: float >>
+                spaces >>
+                b: float >>
+
+                (g, b)
+            )
+        ) >>
+
+        (match gb {
+            Some((g, b)) => Vector3::new(r, g, b),
+            None => Vector3::new(r, r, r)
+        })
+    )
+);
+
+// CIE XYZ to linear sRGB, with the D65 white point.
+fn xyz_to_rgb(c: Vector3) -> Vector3 {
+    Vector3::new(
+        3.2404542 * c.x - 1.5371385 * c.y - 0.4985314 * c.z,
+        -0.969266 * c.x + 1.8760108 * c.y + 0.041556 * c.z,
+        0.0556434 * c.x - 0.2040259 * c.y + 1.0572252 * c.z
+    )
+}
+
+// An RGB color, `xyz` followed by a CIE XYZ color, or `spectral` followed by a reflectance
+// curve file and an optional factor. Spectral curves are not read, giving `None`.
+fn color(input: CompleteStr) -> IResult<CompleteStr, Option<Vector3>> {
+    let (input, _) = spaces(input)?;
+    if let Ok((rest, _)) = terminated!(input, tag!("spectral"), spaces) {
+        return do_parse!(rest, name >> line_end >> (None));
+    }
+
+    let (input, xyz) = opt!(input, terminated!(tag!("xyz"), spaces))?;
+    let (rest, c) = terminated!(input, components, line_end)?;
+    Ok((rest, Some(if xyz.is_some() { xyz_to_rgb(c) } else { c })))
+}
+
+/*
+    Scalars
+*/
+
+named!(scalar<CompleteStr, f32>,
+    do_parse!(
+        spaces >>
+        x: float >>
+        line_end >>
+
+        (x)
+    )
+);
+
+named!(dissolve<CompleteStr, (bool, f32)>,
+    do_parse!(
+        spaces >>
+        halo: opt!(terminated!(tag!("-halo"), spaces)) >>
+        d: float >>
+        line_end >>
+
+        (halo.is_some(), d)
+    )
+);
+
+named!(illumination_model<CompleteStr, u8>,
+    do_parse!(
+        spaces >>
+        illum: map_res!(digit, |s: CompleteStr| s.parse::<u8>()) >>
+        line_end >>
+
+        (illum)
+    )
+);
+
+/*
+    Texture Maps
+*/
+
+enum TextureOption<'a> {
+    Offset(Vector3),
+    Scale(Vector3),
+    Turbulence(Vector3),
+    BumpMultiplier(f32),
+    Clamp(bool),
+    BlendU(bool),
+    BlendV(bool),
+    ColorCorrection(bool),
+    Boost(f32),
+    ValueRange(f32, f32),
+    Channel(ImageChannel),
+    Resolution(u32),
+    Type(CompleteStr<'a>),
+}
+
+// Option values must be followed by more arguments, which keeps a file name
+// starting with a digit from being read as a number.
+named!(option_value<CompleteStr, f32>,
+    preceded!(
+        spaces,
+        terminated!(float, peek!(spaces))
+    )
+);
+
+named!(on_off<CompleteStr, bool>,
+    preceded!(
+        spaces,
+        alt!(
+            value!(true, tag!("on")) |
+            value!(false, tag!("off"))
+        )
+    )
+);
+
+// `u [v [w]]`. Missing components keep the option's default.
+named_args!(option_vector(default: f32)<CompleteStr, Vector3>,
+    do_parse!(
+        u: option_value >>
+        v: opt!(option_value) >>
+        w: opt!(option_value) >>
+
+        (Vector3::new(u, v.unwrap_or(default), w.unwrap_or(default)))
+    )
+);
+
+named!(image_channel<CompleteStr, ImageChannel>,
+    preceded!(
+        spaces,
+        alt!(
+            value!(ImageChannel::Red, tag!("r")) |
+            value!(ImageChannel::Green, tag!("g")) |
+            value!(ImageChannel::Blue, tag!("b")) |
+            value!(ImageChannel::Matte, tag!("m")) |
+            value!(ImageChannel::Luminance, tag!("l")) |
+            value!(ImageChannel::Depth, tag!("z"))
+        )
+    )
+);
+
+named!(texture_option<CompleteStr, TextureOption>,
+    preceded!(
+        spaces,
+        alt!(
+            preceded!(tag!("-o"), map!(call!(option_vector, 0.0), TextureOption::Offset)) |
+            preceded!(tag!("-s"), map!(call!(option_vector, 1.0), TextureOption::Scale)) |
+            preceded!(tag!("-texres"), map!(preceded!(spaces, map_res!(digit, |s: CompleteStr| s.parse::<u32>())), TextureOption::Resolution)) |
+            preceded!(tag!("-type"), map!(preceded!(spaces, word), TextureOption::Type)) |
+            preceded!(tag!("-t"), map!(call!(option_vector, 0.0), TextureOption::Turbulence)) |
+            preceded!(tag!("-bm"), map!(option_value, TextureOption::BumpMultiplier)) |
+            preceded!(tag!("-boost"), map!(option_value, TextureOption::Boost)) |
+            preceded!(tag!("-clamp"), map!(on_off, TextureOption::Clamp)) |
+            preceded!(tag!("-blendu"), map!(on_off, TextureOption::BlendU)) |
+            preceded!(tag!("-blendv"), map!(on_off, TextureOption::BlendV)) |
+            preceded!(tag!("-cc"), map!(on_off, TextureOption::ColorCorrection)) |
+            preceded!(tag!("-imfchan"), map!(image_channel, TextureOption::Channel)) |
+            do_parse!(
+                tag!("-mm") >>
+                base: option_value >>
+                gain: option_value >>
+
+                (TextureOption::ValueRange(base, gain))
+            )
+        )
+    )
+);
+
+named!(texture_map<CompleteStr, TextureMap>,
+    do_parse!(
+        options: many0!(texture_option) >>
+        spaces >>
+        // Anything starting with `-` is an option that failed to parse.
+        not!(tag!("-")) >>
+        path: name >>
+        line_end >>
+
+        ({
+            let mut map = TextureMap::new(&path);
+            for option in options {
+                match option {
+                    TextureOption::Offset(o) => map.offset = o,
+                    TextureOption::Scale(s) => map.scale = s,
+                    TextureOption::Turbulence(t) => map.turbulence = t,
+                    TextureOption::BumpMultiplier(bm) => map.bump_multiplier = Some(bm),
+                    TextureOption::Clamp(clamp) => map.clamp = clamp,
+                    TextureOption::BlendU(blend) => map.blend_u = blend,
+                    TextureOption::BlendV(blend) => map.blend_v = blend,
+                    TextureOption::ColorCorrection(cc) => map.color_correction = cc,
+                    TextureOption::Boost(boost) => map.boost = Some(boost),
+                    TextureOption::ValueRange(base, gain) => {
+                        map.base = base;
+                        map.gain = gain;
+                    },
+                    TextureOption::Channel(channel) => map.channel = Some(channel),
+                    TextureOption::Resolution(resolution) => map.resolution = Some(resolution),
+                    TextureOption::Type(t) => map.reflection_type = Some(t.to_string()),
+                }
+            }
+            map
+        })
+    )
+);
+
+/*
+    Errors
+*/
+
+/// The reason an MTL file could not be parsed and where it failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MtlError {
+    /// A material property appeared before the first `newmtl`.
+    MissingNewmtl(Location),
+    /// The arguments of a statement could not be read.
+    InvalidStatement {
+        keyword: String,
+        location: Location,
+    },
+    UnknownStatement(Location),
+}
+
+impl MtlError {
+    pub fn location(&self) -> &Location {
+        match *self {
+            MtlError::MissingNewmtl(ref l) |
+            MtlError::InvalidStatement { location: ref l, .. } |
+            MtlError::UnknownStatement(ref l) => l
+        }
+    }
+}
+
+impl fmt::Display for MtlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            MtlError::MissingNewmtl(_) => "material property before newmtl".to_string(),
+            MtlError::InvalidStatement { ref keyword, .. } => format!("error reading {}", keyword),
+            MtlError::UnknownStatement(_) => "unrecognized statement".to_string(),
+        };
+        write!(f, "Unable to parse MTL file: {} at {}", reason, self.location())
+    }
+}
+
+impl Error for MtlError {}
+
+/*
+    MTL File
+*/
+
+/// Options controlling how an MTL file is read.
+#[derive(Debug, Clone, Default)]
+pub struct MtlOptions {
+    /// Fail with `MtlError::UnknownStatement` on statements the parser does not recognize.
+    /// Otherwise they are skipped.
+    pub strict: bool,
+}
+
+// Parses the arguments following a statement's keyword.
+fn arguments<'a, O>(
+    data: &str,
+    keyword: CompleteStr,
+    input: CompleteStr<'a>,
+    parser: fn(CompleteStr<'a>) -> IResult<CompleteStr<'a>, O>
+) -> Result<(CompleteStr<'a>, O), MtlError> {
+    parser(input).map_err(|e| MtlError::InvalidStatement {
+        keyword: keyword.to_string(),
+        location: error_location(data, &e, input),
+    })
+}
+
+pub fn parse_mtl_file(data: &str) -> Result<Vec<Material>, MtlError> {
+    parse_mtl_file_with_options(data, &MtlOptions::default())
+}
+
+pub fn parse_mtl_file_with_options(data: &str, options: &MtlOptions) -> Result<Vec<Material>, MtlError> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    let mut remainder = CompleteStr(data);
+    loop {
+        if let Ok((r, _)) = ignore_lines(remainder) {
+            remainder = r;
+        }
+        if remainder.is_empty() {
+            break;
+        }
+
+        let (args, keyword) = match keyword(remainder) {
+            Ok(x) => x,
+            Err(e) => return Err(MtlError::UnknownStatement(error_location(data, &e, remainder)))
+        };
+
+        if keyword.0 == "newmtl" {
+            let (r, name) = arguments(data, keyword, args, material_name)?;
+            materials.push(Material::new(&name));
+            remainder = r;
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(m) => m,
+            // A final comment or whitespace without a line ending.
+            None if only_ignored_lines(remainder) => break,
+            None => return Err(MtlError::MissingNewmtl(Location::new(data, statement_start(remainder))))
+        };
+
+        remainder = match keyword.0 {
+            "Ka" => {
+                let (r, c) = arguments(data, keyword, args, color)?;
+                material.ambient = c;
+                r
+            },
+            "Kd" => {
+                let (r, c) = arguments(data, keyword, args, color)?;
+                material.diffuse = c;
+                r
+            },
+            "Ks" => {
+                let (r, c) = arguments(data, keyword, args, color)?;
+                material.specular = c;
+                r
+            },
+            "Ke" => {
+                let (r, c) = arguments(data, keyword, args, color)?;
+                material.emissive = c;
+                r
+            },
+            "Tf" => {
+                let (r, c) = arguments(data, keyword, args, color)?;
+                material.transmission_filter = c;
+                r
+            },
+            "Ns" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.shininess = Some(x);
+                r
+            },
+            "Ni" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.optical_density = Some(x);
+                r
+            },
+            "d" => {
+                let (r, (halo, d)) = arguments(data, keyword, args, dissolve)?;
+                material.dissolve = Some(d);
+                material.dissolve_halo = halo;
+                r
+            },
+            "Tr" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.dissolve = Some(1.0 - x);
+                r
+            },
+            "sharpness" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.sharpness = Some(x);
+                r
+            },
+            "illum" => {
+                let (r, illum) = arguments(data, keyword, args, illumination_model)?;
+                material.illumination_model = Some(illum);
+                r
+            },
+            "Pr" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.roughness = Some(x);
+                r
+            },
+            "Pm" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.metallic = Some(x);
+                r
+            },
+            "Ps" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.sheen = Some(x);
+                r
+            },
+            "Pc" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.clearcoat_thickness = Some(x);
+                r
+            },
+            "Pcr" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.clearcoat_roughness = Some(x);
+                r
+            },
+            "aniso" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.anisotropy = Some(x);
+                r
+            },
+            "anisor" => {
+                let (r, x) = arguments(data, keyword, args, scalar)?;
+                material.anisotropy_rotation = Some(x);
+                r
+            },
+            "map_Ka" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.ambient_map = Some(map);
+                r
+            },
+            "map_Kd" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.diffuse_map = Some(map);
+                r
+            },
+            "map_Ks" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.specular_map = Some(map);
+                r
+            },
+            "map_Ke" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.emissive_map = Some(map);
+                r
+            },
+            "map_Ns" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.shininess_map = Some(map);
+                r
+            },
+            "map_d" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.dissolve_map = Some(map);
+                r
+            },
+            "map_bump" | "map_Bump" | "bump" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.bump_map = Some(map);
+                r
+            },
+            "disp" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.displacement_map = Some(map);
+                r
+            },
+            "decal" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.decal_map = Some(map);
+                r
+            },
+            "map_Pr" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.roughness_map = Some(map);
+                r
+            },
+            "map_Pm" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.metallic_map = Some(map);
+                r
+            },
+            "map_Ps" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.sheen_map = Some(map);
+                r
+            },
+            "norm" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.normal_map = Some(map);
+                r
+            },
+            "refl" => {
+                let (r, map) = arguments(data, keyword, args, texture_map)?;
+                material.reflection_maps.push(map);
+                r
+            },
+            _ if only_ignored_lines(remainder) => break,
+            _ if options.strict => return Err(MtlError::UnknownStatement(Location::new(data, statement_start(remainder)))),
+            _ => match unknown_statement(remainder) {
+                Ok((r, _)) => r,
+                Err(e) => return Err(MtlError::UnknownStatement(error_location(data, &e, remainder)))
+            }
+        };
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        let input = CompleteStr(" 0.800000 0.500000 0.250000\n");
+        let expected_remainder = CompleteStr("");
+
+        match color(input) {
+            Ok((remainder, c)) => {
+                let c = c.unwrap();
+                assert_eq!(remainder, expected_remainder);
+                assert_eq!(c.x, 0.8);
+                assert_eq!(c.y, 0.5);
+                assert_eq!(c.z, 0.25);
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_color_single_value() {
+        let input = CompleteStr(" 0.5 # grey\n");
+        let expected_remainder = CompleteStr("");
+
+        match color(input) {
+            Ok((remainder, c)) => {
+                let c = c.unwrap();
+                assert_eq!(remainder, expected_remainder);
+                assert_eq!(c.x, 0.5);
+                assert_eq!(c.y, 0.5);
+                assert_eq!(c.z, 0.5);
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_color_xyz() {
+        match color(CompleteStr(" xyz 0.9505 1.0 1.089\n")) {
+            Ok((remainder, c)) => {
+                let c = c.unwrap();
+                assert_eq!(remainder, CompleteStr(""));
+                assert!((c.x - 1.0).abs() < 1e-3);
+                assert!((c.y - 1.0).abs() < 1e-3);
+                assert!((c.z - 1.0).abs() < 1e-3);
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+
+        match color(CompleteStr(" xyz 0\n")) {
+            Ok((_, c)) => assert_eq!(c.unwrap().y, 0.0),
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_color_spectral() {
+        assert_eq!(color(CompleteStr(" spectral ident.rfl 0.8\n")).map(|(r, c)| (r, c.is_none())),
+                   Ok((CompleteStr(""), true)));
+        assert_eq!(color(CompleteStr(" spectral ident.rfl\n")).map(|(r, c)| (r, c.is_none())),
+                   Ok((CompleteStr(""), true)));
+    }
+
+    #[test]
+    fn test_parse_mtl_file_spectral_and_xyz_colors() {
+        let s = "newmtl Metal
+Ka spectral copper.rfl
+Kd xyz 0.5
+Ks 0.9
+Tf xyz 0.9505 1.0 1.089
+";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        let m = &materials[0];
+        assert!(m.ambient.is_none());
+        assert!(m.diffuse.is_some());
+        assert_eq!(m.specular.unwrap().x, 0.9);
+        assert!((m.transmission_filter.unwrap().z - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_parse_dissolve() {
+        let expected_remainder = CompleteStr("");
+
+        assert_eq!(dissolve(CompleteStr(" 0.5\n")), Ok((expected_remainder, (false, 0.5))));
+        assert_eq!(dissolve(CompleteStr(" -halo 0.5\n")), Ok((expected_remainder, (true, 0.5))));
+    }
+
+    #[test]
+    fn test_parse_mtl_file() {
+        let s = include_str!("../assets/cube_uv.mtl");
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        assert_eq!(materials.len(), 1);
+        let m = &materials[0];
+        assert_eq!(m.name, "Material");
+        assert_eq!(m.shininess, Some(225.0));
+        let ka = m.ambient.unwrap();
+        assert_eq!((ka.x, ka.y, ka.z), (1.0, 1.0, 1.0));
+        let kd = m.diffuse.unwrap();
+        assert_eq!((kd.x, kd.y, kd.z), (0.8, 0.8, 0.8));
+        let ks = m.specular.unwrap();
+        assert_eq!((ks.x, ks.y, ks.z), (0.5, 0.5, 0.5));
+        let ke = m.emissive.unwrap();
+        assert_eq!((ke.x, ke.y, ke.z), (0.0, 0.0, 0.0));
+        assert_eq!(m.optical_density, Some(1.45));
+        assert_eq!(m.dissolve, Some(1.0));
+        assert_eq!(m.illumination_model, Some(2));
+        assert!(m.transmission_filter.is_none());
+    }
+
+    #[test]
+    fn test_parse_mtl_file_multiple_materials() {
+        let s = "newmtl Red\nKd 1 0 0\nTr 0.25\n\nnewmtl Glass\n  Kd 0.9\n  d -halo 0.1\n  Ni 1.5\n";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].name, "Red");
+        assert_eq!(materials[0].dissolve, Some(0.75));
+        assert_eq!(materials[1].name, "Glass");
+        assert_eq!(materials[1].diffuse.unwrap().y, 0.9);
+        assert_eq!(materials[1].dissolve, Some(0.1));
+        assert!(materials[1].dissolve_halo);
+        assert_eq!(materials[1].optical_density, Some(1.5));
+        assert!(materials[1].shininess.is_none());
+    }
+
+    #[test]
+    fn test_parse_mtl_file_invalid_statement() {
+        let s = "newmtl Red\nKd 1 zero 0\n";
+        let expected_error = MtlError::InvalidStatement {
+            keyword: "Kd".to_string(),
+            location: Location {
+                line: 2,
+                column: 6,
+                text: "Kd 1 zero 0".to_string(),
+            },
+        };
+
+        assert_eq!(parse_mtl_file(s).err(), Some(expected_error));
+    }
+
+    #[test]
+    fn test_parse_mtl_file_missing_newmtl() {
+        let s = "# no material\nKd 1 0 0\n";
+        let expected_location = Location {
+            line: 2,
+            column: 1,
+            text: "Kd 1 0 0".to_string(),
+        };
+
+        assert_eq!(parse_mtl_file(s).err(), Some(MtlError::MissingNewmtl(expected_location)));
+    }
+
+    #[test]
+    fn test_parse_mtl_file_unknown_statement() {
+        let s = "newmtl Red\n  bogus 1\n";
+        let expected_location = Location {
+            line: 2,
+            column: 3,
+            text: "  bogus 1".to_string(),
+        };
+
+        let options = MtlOptions {
+            strict: true,
+        };
+        assert_eq!(parse_mtl_file_with_options(s, &options).err(), Some(MtlError::UnknownStatement(expected_location)));
+    }
+
+    #[test]
+    fn test_parse_mtl_file_skips_unknown_statements() {
+        let s = "newmtl Red\nKm 0.5\nKd 1 0 0\n\
+                 map_refl -o 1 1 env.png\n\
+                 vendor_key \\\n  value\n\
+                 Ns 10\n";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials[0].diffuse.unwrap().x, 1.0);
+        assert_eq!(materials[0].shininess, Some(10.0));
+    }
+
+    #[test]
+    fn test_parse_texture_map() {
+        let input = CompleteStr(" wood.png\n");
+
+        match texture_map(input) {
+            Ok((remainder, map)) => {
+                assert_eq!(remainder, CompleteStr(""));
+                assert_eq!(map.path, "wood.png");
+                assert_eq!((map.scale.x, map.scale.y, map.scale.z), (1.0, 1.0, 1.0));
+                assert!(map.blend_u);
+                assert!(!map.clamp);
+                assert!(map.bump_multiplier.is_none());
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_texture_map_options() {
+        let input = CompleteStr(" -o 0.5 0.25 -s 2 2 1 -t 0.1 -clamp on -blendu off -bm 0.75 -imfchan l -mm 0.1 0.9 -texres 512 2.png\n");
+
+        match texture_map(input) {
+            Ok((remainder, map)) => {
+                assert_eq!(remainder, CompleteStr(""));
+                assert_eq!(map.path, "2.png");
+                assert_eq!((map.offset.x, map.offset.y, map.offset.z), (0.5, 0.25, 0.0));
+                assert_eq!((map.scale.x, map.scale.y, map.scale.z), (2.0, 2.0, 1.0));
+                assert_eq!((map.turbulence.x, map.turbulence.y, map.turbulence.z), (0.1, 0.0, 0.0));
+                assert!(map.clamp);
+                assert!(!map.blend_u);
+                assert!(map.blend_v);
+                assert_eq!(map.bump_multiplier, Some(0.75));
+                assert_eq!(map.channel, Some(ImageChannel::Luminance));
+                assert_eq!((map.base, map.gain), (0.1, 0.9));
+                assert_eq!(map.resolution, Some(512));
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_mtl_file_texture_maps() {
+        let s = "newmtl Brick\n\
+                 map_Kd brick.png\n\
+                 map_Ks -s 4 4 brick_spec.png\n\
+                 map_Ns brick_rough.png\n\
+                 map_d -imfchan m brick_alpha.png\n\
+                 bump -bm 0.5 brick_bump.png\n\
+                 disp brick_disp.png\n\
+                 decal brick_decal.png\n\
+                 refl -type cube_top sky_top.png\n\
+                 refl -type cube_bottom sky_bottom.png\n";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        let m = &materials[0];
+        assert_eq!(m.diffuse_map.as_ref().unwrap().path, "brick.png");
+        assert_eq!(m.specular_map.as_ref().unwrap().scale.x, 4.0);
+        assert_eq!(m.shininess_map.as_ref().unwrap().path, "brick_rough.png");
+        assert_eq!(m.dissolve_map.as_ref().unwrap().channel, Some(ImageChannel::Matte));
+        assert_eq!(m.bump_map.as_ref().unwrap().bump_multiplier, Some(0.5));
+        assert_eq!(m.displacement_map.as_ref().unwrap().path, "brick_disp.png");
+        assert_eq!(m.decal_map.as_ref().unwrap().path, "brick_decal.png");
+        assert_eq!(m.reflection_maps.len(), 2);
+        assert_eq!(m.reflection_maps[1].reflection_type, Some("cube_bottom".to_string()));
+        assert!(m.ambient_map.is_none());
+    }
+
+    #[test]
+    fn test_parse_mtl_file_blender_bump_map() {
+        let s = "newmtl A\nmap_Bump -bm 1.0 n.png\n";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        let bump = materials[0].bump_map.as_ref().unwrap();
+        assert_eq!(bump.path, "n.png");
+        assert_eq!(bump.bump_multiplier, Some(1.0));
+    }
+
+    #[test]
+    fn test_parse_mtl_file_invalid_texture_option() {
+        let s = "newmtl Brick\nmap_Kd -clamp maybe brick.png\n";
+
+        match parse_mtl_file(s) {
+            Err(MtlError::InvalidStatement { keyword, location }) => {
+                assert_eq!(keyword, "map_Kd");
+                assert_eq!(location.line, 2);
+            },
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_parse_mtl_file_pbr() {
+        let s = "newmtl Metal\n\
+                 Kd 0.8 0.8 0.8\n\
+                 Pr 0.25\n\
+                 Pm 1.0\n\
+                 Ps 0.1\n\
+                 Pc 0.5\n\
+                 Pcr 0.03\n\
+                 aniso 0.4\n\
+                 anisor 0.2\n\
+                 map_Pr metal_roughness.png\n\
+                 map_Pm metal_metallic.png\n\
+                 norm -bm 2 metal_normal.png\n";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        let m = &materials[0];
+        assert_eq!(m.roughness, Some(0.25));
+        assert_eq!(m.metallic, Some(1.0));
+        assert_eq!(m.sheen, Some(0.1));
+        assert_eq!(m.clearcoat_thickness, Some(0.5));
+        assert_eq!(m.clearcoat_roughness, Some(0.03));
+        assert_eq!(m.anisotropy, Some(0.4));
+        assert_eq!(m.anisotropy_rotation, Some(0.2));
+        assert_eq!(m.roughness_map.as_ref().unwrap().path, "metal_roughness.png");
+        assert_eq!(m.metallic_map.as_ref().unwrap().path, "metal_metallic.png");
+        assert_eq!(m.normal_map.as_ref().unwrap().bump_multiplier, Some(2.0));
+        assert!(m.sheen_map.is_none());
+    }
+
+    #[test]
+    fn test_parse_mtl_file_tab_separated() {
+        let s = "newmtl\tRed\n\
+                 \tKd\t1.0\t0.0 0.0\t\n\
+                 \td\t-halo\t0.5\n\
+                 \tmap_Kd\t-s\t2 2\tred.png\n\
+                 \tillum\t2";
+
+        let materials = parse_mtl_file(s).unwrap();
+
+        let m = &materials[0];
+        assert_eq!(m.name, "Red");
+        assert_eq!(m.diffuse.unwrap().x, 1.0);
+        assert_eq!(m.dissolve, Some(0.5));
+        assert_eq!(m.diffuse_map.as_ref().unwrap().path, "red.png");
+        assert_eq!(m.diffuse_map.as_ref().unwrap().scale.y, 2.0);
+        assert_eq!(m.illumination_model, Some(2));
+    }
+}
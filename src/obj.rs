@@ -1,428 +1,1409 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
 use nom::*;
 use nom::types::CompleteStr;
 use tdmath::Vector3;
+use crate::freeform::{Basis, ControlPoint, Curve, ParameterCurve, Surface, TrimCurve};
 use crate::model::*;
+use crate::parse::{current_position, error_location, ignore_lines, keyword, line_end, name, only_ignored_lines, spaces, statement_start, unknown_statement, word, Location};
+use crate::triangulate::{face_normal, triangulate, Triangulation};
 
 /*
-    Basic Parsers
+    Object Name
 */
 
-named!(space<CompleteStr, CompleteStr>,
-    tag!(" ")
-);
-
-fn is_space(c: char) -> bool {
-    c == ' '
-}
+named!(object_name<CompleteStr, Cow<str>>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("o") >>
+        spaces >>
+        n: name >>
+        line_end >>
 
-named!(spaces<CompleteStr, CompleteStr>,
-    take_while1!(is_space)
+        (n)
+    )
 );
 
-fn is_name_char(c: char) -> bool {
-    c.is_alphabetic() || c.is_digit(10) || c == '.' || c == '_'
+/*
+    Vertex
+*/
+
+// The numbers after `x y z`: either a weight `w`, or an RGB vertex color as written by
+// MeshLab and ZBrush. A weight and a color together are not accepted.
+fn vertex_extras(extras: Vec<f32>) -> Option<(f32, Option<Vector3>)> {
+    match extras.len() {
+        0 => Some((1.0, None)),
+        1 => Some((extras[0], None)),
+        3 => Some((1.0, Some(Vector3::new(extras[0], extras[1], extras[2])))),
+        _ => None
+    }
 }
 
-named!(name<CompleteStr, CompleteStr>,
-    take_while1!(is_name_char)
-);
+named!(vertex<CompleteStr, (Vector3, f32, Option<Vector3>)>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("v") >>
+        spaces >>
+        x: float >>
+        spaces >>
+        y: float >>
+        spaces >>
+        z: float >>
+        extras: map_opt!(many0!(preceded!(spaces, float)), vertex_extras) >>
+        line_end >>
 
-named!(filename<CompleteStr, CompleteStr>,
-    take_while1!(is_name_char)
+        (Vector3::new(x, y, z), extras.0, extras.1)
+    )
 );
 
-named!(line_end<CompleteStr, CompleteStr>,
-    preceded!(
-        opt!(spaces),
-        alt!(line_ending | comment)
+/*
+    Texture Coordinates
+*/
+
+named!(texture_coordinates<CompleteStr, Vector3>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("vt") >>
+        spaces >>
+        x: float >>
+        spaces >>
+        y: float >>
+        z: opt!(preceded!(spaces, float)) >>
+        line_end >>
+
+        (Vector3::new(x, y, z.unwrap_or(0.0)))
     )
 );
 
-named!(empty_line<CompleteStr, CompleteStr>,
-    preceded!(
-        opt!(spaces),
-        line_ending
+/*
+    Vertex Normals
+*/
+
+named!(vertex_normal<CompleteStr, Vector3>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("vn") >>
+        spaces >>
+        x: float >>
+        spaces >>
+        y: float >>
+        spaces >>
+        z: float >>
+        line_end >>
+
+        (Vector3::new(x, y, z))
     )
 );
 
 /*
-    Comments
+    Materials
 */
 
-named!(comment<CompleteStr, CompleteStr>,
+// The whole argument is kept, since it may be one file name containing spaces
+// or several file names separated by spaces.
+named!(material_file<CompleteStr, Cow<str>>,
     do_parse!(
-        tag!("#") >>
-        comment: take_until_either!("\r\n") >>
-        opt!(tag!("\r")) >>
-        tag!("\n") >>
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("mtllib") >>
+        spaces >>
+        names: name >>
+        line_end >>
 
-        (comment)
+        (names)
     )
 );
 
-named!(ignore_line<CompleteStr, CompleteStr>,
-    alt!(empty_line | comment)
-);
+named!(usemtl<CompleteStr, Cow<str>>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("usemtl") >>
+        spaces >>
+        name: name >>
+        line_end >>
 
-named!(ignore_lines<CompleteStr, Vec<CompleteStr>>,
-    many0!(comment)
+        (name)
+    )
 );
 
 /*
-    Object Name
+    Smooth Shading
 */
 
-named!(object_name<CompleteStr, Option<CompleteStr>>,
-    opt!(
-        do_parse!(
-            opt!(many0!(line_end)) >>
-            opt!(spaces) >>
-            tag!("o") >>
-            spaces >>
-            n: name >>
-            line_end >>
-
-            (n)
-        )
+// `s off` and `s 0` turn smoothing off. `s on` is treated as group 1.
+named!(smoothing_group<CompleteStr, u32>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("s") >>
+        spaces >>
+        group: alt!(
+            value!(0, tag!("off")) |
+            value!(1, tag!("on")) |
+            map_res!(digit, |s: CompleteStr| s.parse::<u32>())
+        ) >>
+        line_end >>
+
+        (group)
     )
 );
 
 /*
-    Vertex
+    Polygon Group
 */
 
-named!(vertex<CompleteStr, Vector3>,
+// A face can belong to several groups at once. `g` on its own selects the default group.
+named!(polygon_group<CompleteStr, Vec<CompleteStr>>,
     do_parse!(
         opt!(many0!(line_end)) >>
         opt!(spaces) >>
-        tag!("v ") >>
-        opt!(spaces) >>
-        x: float >>
-        spaces >>
-        y: float >>
-        spaces >>
-        z: float >>
+        tag!("g") >>
+        names: many0!(preceded!(spaces, word)) >>
         line_end >>
 
-        (Vector3::new(x, y, z))
+        (if names.is_empty() {
+            vec![CompleteStr(DEFAULT_GROUP_NAME)]
+        } else {
+            names
+        })
+    )
+);
+
+/*
+    Face
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ElementKind {
+    Face,
+    Line,
+    Point,
+}
+
+// A face, line or point element.
+struct FaceIndexed<'a> {
+    pub kind: ElementKind,
+    pub vertexes: Vec<isize>,
+    pub texture_coordinates: Vec<Option<isize>>,
+    pub vertex_normals: Vec<Option<isize>>,
+    // Where each `v/vt/vn` corner starts, so errors can point at the index.
+    pub corners: Vec<CompleteStr<'a>>,
+    // Index into `ObjData::group_sets`, assigned once the face is read.
+    pub groups: usize,
+    // The material selected by the last `usemtl`, assigned once the face is read.
+    pub material: Option<Cow<'a, str>>,
+    // The smoothing group selected by the last `s`, assigned once the face is read. 0 is none.
+    pub smoothing_group: u32,
+}
+
+// Indexes are 1-based. Negative indexes count back from the end of the list read so far.
+named!(index<CompleteStr, isize>,
+    map_res!(
+        recognize!(preceded!(opt!(tag!("-")), digit)),
+        |s: CompleteStr| s.parse::<isize>()
     )
 );
 
-named!(vertex_list<CompleteStr, Vec<Vector3>>,
+// Accepts the four index forms: v, v/vt, v//vn and v/vt/vn.
+named!(face_index<CompleteStr, (isize, Option<isize>, Option<isize>)>,
     do_parse!(
-        opt!(many0!(ignore_line)) >>
-        v: many0!(vertex) >>
+        v: index >>
+        t: opt!(preceded!(tag!("/"), opt!(index))) >>
+        vn: cond!(t.is_some(), opt!(preceded!(tag!("/"), index))) >>
 
-        (v)
+        (v, t.and_then(|t| t), vn.and_then(|vn| vn))
     )
 );
 
-/*
-    Texture Coordinates
-*/
+// An element corner's indexes, with the text where the corner starts.
+type Corner<'a> = (CompleteStr<'a>, (isize, Option<isize>, Option<isize>));
 
-named!(texture_coordinates<CompleteStr, Vector3>,
+named!(face_corner<CompleteStr, Corner>,
+    pair!(current_position, face_index)
+);
+
+named!(face<CompleteStr, FaceIndexed>,
     do_parse!(
         opt!(many0!(line_end)) >>
         opt!(spaces) >>
-        tag!("vt") >>
+        tag!("f") >>
         spaces >>
-        x: float >>
+        i1: face_corner >>
         spaces >>
-        y: float >>
-        opt!(space) >>
-        opt!(float) >>
+        i2: face_corner >>
+        spaces >>
+        i3: face_corner >>
+        rest: many0!(preceded!(spaces, face_corner)) >>
         line_end >>
 
-        (Vector3::new(x, y, 0.0))
+        (element(ElementKind::Face, [i1, i2, i3].iter().chain(rest.iter()).cloned().collect()))
     )
 );
 
-named!(texture_coordinate_list<CompleteStr, Vec<Vector3>>,
+fn element(kind: ElementKind, corners: Vec<Corner>) -> FaceIndexed {
+    FaceIndexed {
+        kind,
+        vertexes: corners.iter().map(|c| (c.1).0).collect(),
+        texture_coordinates: corners.iter().map(|c| (c.1).1).collect(),
+        vertex_normals: corners.iter().map(|c| (c.1).2).collect(),
+        corners: corners.iter().map(|c| c.0).collect(),
+        groups: 0,
+        material: None,
+        smoothing_group: 0,
+    }
+}
+
+/*
+    Lines and Points
+*/
+
+// Lines accept the v and v/vt index forms.
+named!(line_index<CompleteStr, (isize, Option<isize>, Option<isize>)>,
     do_parse!(
-        opt!(many0!(ignore_line)) >>
-        uv: many0!(texture_coordinates) >>
+        v: index >>
+        t: opt!(preceded!(tag!("/"), index)) >>
 
-        (uv)
+        (v, t, None)
     )
 );
 
-/*
-    Vertex Normals
-*/
+named!(line_corner<CompleteStr, Corner>,
+    pair!(current_position, line_index)
+);
 
-named!(vertex_normal<CompleteStr, Vector3>,
+// A polyline through two or more vertices.
+named!(line<CompleteStr, FaceIndexed>,
     do_parse!(
         opt!(many0!(line_end)) >>
         opt!(spaces) >>
-        tag!("vn") >>
-        spaces >>
-        x: float >>
+        tag!("l") >>
         spaces >>
-        y: float >>
+        i1: line_corner >>
         spaces >>
-        z: float >>
+        i2: line_corner >>
+        rest: many0!(preceded!(spaces, line_corner)) >>
         line_end >>
 
-        (Vector3::new(x, y, z))
+        (element(ElementKind::Line, [i1, i2].iter().chain(rest.iter()).cloned().collect()))
     )
 );
 
-named!(vertex_normal_list<CompleteStr, Vec<Vector3>>,
+named!(point<CompleteStr, FaceIndexed>,
     do_parse!(
-        opt!(many0!(ignore_line)) >>
-        vn: many0!(vertex_normal) >>
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("p") >>
+        vertexes: many1!(preceded!(spaces, pair!(current_position, index))) >>
+        line_end >>
 
-        (vn)
+        (element(ElementKind::Point, vertexes.iter().map(|&(s, v)| (s, (v, None, None))).collect()))
     )
 );
 
 /*
-    Materials
+    Free-form Geometry
 */
 
-named!(material_file<CompleteStr, Option<CompleteStr>>,
-    opt!(
-        do_parse!(
-            opt!(many0!(line_end)) >>
-            opt!(spaces) >>
-            tag!("mtllib") >>
-            spaces >>
-            name: filename >>
-            line_end >>
-
-            (name)
-        )
+named!(parameter_vertex<CompleteStr, Vector3>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("vp") >>
+        spaces >>
+        u: float >>
+        v: opt!(preceded!(spaces, float)) >>
+        w: opt!(preceded!(spaces, float)) >>
+        line_end >>
+
+        (Vector3::new(u, v.unwrap_or(0.0), w.unwrap_or(1.0)))
     )
 );
 
-named!(usemtl<CompleteStr, Option<CompleteStr>>,
-    opt!(
-        do_parse!(
-            opt!(many0!(line_end)) >>
-            opt!(spaces) >>
-            tag!("usemtl") >>
-            spaces >>
-            name: name >>
-            line_end >>
-
-            (name)
-        )
+named!(basis<CompleteStr, Basis>,
+    alt!(
+        value!(Basis::Bezier, tag!("bezier")) |
+        value!(Basis::BSpline, tag!("bspline"))
     )
 );
 
-/*
-    Smooth Shading
-*/
-
-fn str_to_bool(s: CompleteStr) -> Result<bool, CompleteStr> {
-    if s == CompleteStr("on") || s == CompleteStr("1") {
-        Ok(true)
-    } else if s == CompleteStr("off") || s == CompleteStr("0") {
-        Ok(false)
-    } else {
-        Err(CompleteStr("Cannot convert string to bool"))
-    }
-}
-
-named!(smooth_shading<CompleteStr, Option<bool>>,
-    opt!(
-        do_parse!(
-            opt!(many0!(line_end)) >>
-            opt!(spaces) >>
-            tag!("s") >>
-            spaces >>
-            b: map_res!(take_until!("\n"), str_to_bool) >>
-            line_end >>
+named!(curve_type<CompleteStr, (Basis, bool)>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("cstype") >>
+        spaces >>
+        rational: opt!(terminated!(tag!("rat"), spaces)) >>
+        b: basis >>
+        line_end >>
 
-            (b)
-        )
+        (b, rational.is_some())
     )
 );
 
-/*
-    Polygon Group
-*/
+named!(degree_value<CompleteStr, usize>,
+    map_res!(digit, |s: CompleteStr| s.parse::<usize>())
+);
+
+named!(degree<CompleteStr, (usize, usize)>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("deg") >>
+        spaces >>
+        u: degree_value >>
+        v: opt!(preceded!(spaces, degree_value)) >>
+        line_end >>
 
-named!(polygon_group<CompleteStr, Option<CompleteStr>>,
-    opt!(
-        do_parse!(
-            opt!(many0!(line_end)) >>
-            opt!(spaces) >>
-            tag!("g") >>
-            spaces >>
-            n: name >>
-            line_end >>
-
-            (n)
-        )
+        (u, v.unwrap_or(0))
     )
 );
 
-/*
-    Face
-*/
+named!(curve<CompleteStr, FreeFormIndexed>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        source: current_position >>
+        tag!("curv") >>
+        spaces >>
+        start: float >>
+        spaces >>
+        end: float >>
+        control_points: many1!(preceded!(spaces, index)) >>
+        line_end >>
 
-struct FaceIndexed {
-    pub vertexes: [usize; 3],
-    pub texture_coordinates: [Option<usize>; 3],
-    pub vertex_normals: [usize; 3],
-}
+        (FreeFormIndexed::new(source, FreeFormKind::Curve, (start, end), (0.0, 0.0), control_points))
+    )
+);
 
-named!(face_index<CompleteStr, (usize, Option<usize>, usize)>,
+named!(parameter_curve<CompleteStr, FreeFormIndexed>,
     do_parse!(
-        v: digit >>
-        opt!(tag!("/")) >>
-        t: opt!(digit) >>
-        opt!(tag!("/")) >>
-        vn: digit >>
-
-        (v.parse::<usize>().unwrap(),
-        match t {
-            Some(t) => Some(t.parse::<usize>().unwrap()),
-            None => None
-        },
-        vn.parse::<usize>().unwrap())
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        source: current_position >>
+        tag!("curv2") >>
+        control_points: many1!(preceded!(spaces, index)) >>
+        line_end >>
+
+        (FreeFormIndexed::new(source, FreeFormKind::ParameterCurve, (0.0, 0.0), (0.0, 0.0), control_points))
     )
 );
 
-named!(face<CompleteStr, FaceIndexed>,
+// Texture coordinate and normal indexes on surface control points are accepted but not kept.
+named!(surface<CompleteStr, FreeFormIndexed>,
     do_parse!(
         opt!(many0!(line_end)) >>
         opt!(spaces) >>
-        tag!("f") >>
+        source: current_position >>
+        tag!("surf") >>
         spaces >>
-        i1: face_index >>
+        start_u: float >>
         spaces >>
-        i2: face_index >>
+        end_u: float >>
         spaces >>
-        i3: face_index >>
+        start_v: float >>
+        spaces >>
+        end_v: float >>
+        control_points: many1!(preceded!(spaces, face_index)) >>
         line_end >>
 
-        (FaceIndexed {
-            vertexes: [i1.0, i2.0, i3.0],
-            texture_coordinates: [i1.1, i2.1, i3.1],
-            vertex_normals: [i1.2, i2.2, i3.2]
-        })
+        (FreeFormIndexed::new(source, FreeFormKind::Surface, (start_u, end_u), (start_v, end_v),
+                              control_points.iter().map(|i| i.0).collect()))
     )
 );
 
-named!(face_list<CompleteStr, Vec<FaceIndexed>>,
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    U,
+    V,
+}
+
+named!(parameter_values<CompleteStr, (Direction, Vec<f32>)>,
     do_parse!(
-        opt!(many0!(ignore_line)) >>
-        f: many0!(face) >>
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("parm") >>
+        spaces >>
+        direction: alt!(value!(Direction::U, tag!("u")) | value!(Direction::V, tag!("v"))) >>
+        values: many1!(preceded!(spaces, float)) >>
+        line_end >>
 
-        (f)
+        (direction, values)
     )
 );
 
-fn discard_comments(data: CompleteStr) -> CompleteStr {
-    match ignore_lines(data) {
-        Ok((remainder, _)) => remainder,
-        Err(_) => panic!("Unable to parse OBJ file: error reading leading comments")
-    }
+// `trim` and `hole` list `u0 u1 curv2d` triples, each a part of a `curv2` curve.
+fn trim_loop<'a>(input: CompleteStr<'a>, keyword: &'static str) -> IResult<CompleteStr<'a>, Vec<(f32, f32, isize)>> {
+    do_parse!(input,
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!(keyword) >>
+        curves: many1!(
+            do_parse!(
+                spaces >>
+                start: float >>
+                spaces >>
+                end: float >>
+                spaces >>
+                curve: index >>
+
+                (start, end, curve)
+            )
+        ) >>
+        line_end >>
+
+        (curves)
+    )
 }
 
-pub fn parse_obj_file(data: &str) -> Model {
-    // Leading comments
-    let remainder = discard_comments(CompleteStr(data));
+named!(trim<CompleteStr, Vec<(f32, f32, isize)>>,
+    call!(trim_loop, "trim")
+);
 
-    let (remainder, _) = match material_file(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading material file")
-    };
+named!(hole<CompleteStr, Vec<(f32, f32, isize)>>,
+    call!(trim_loop, "hole")
+);
 
-    let (remainder, obj_name) = match object_name(remainder) {
-        Ok((remainder, obj_name)) => {
-            match obj_name {
-                Some(x) => (remainder, x),
-                None => (remainder, CompleteStr("Object"))
-            }
-        },
-        Err(_) => panic!("Unable to parse OBJ file: error reading object name")
-    };
-    
-    let (remainder, vertex_positions) = match vertex_list(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading vertex positions")
-    };
+named!(end<CompleteStr, CompleteStr>,
+    do_parse!(
+        opt!(many0!(line_end)) >>
+        opt!(spaces) >>
+        tag!("end") >>
+        line_end >>
 
-    let (remainder, uvs) = match texture_coordinate_list(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading UV coordinates")
-    };
+        (CompleteStr(""))
+    )
+);
 
-    let (remainder, vertex_normals) = match vertex_normal_list(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading vertex normals")
-    };
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FreeFormKind {
+    Curve,
+    ParameterCurve,
+    Surface,
+}
 
-    let (remainder, _) = match usemtl(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading usemtl")
-    };
+// A `curv`, `curv2` or `surf` statement and the body statements up to its `end`.
+struct FreeFormIndexed<'a> {
+    source: CompleteStr<'a>,
+    kind: FreeFormKind,
+    // Assigned from the `cstype` and `deg` in effect once the statement is read.
+    basis: Basis,
+    rational: bool,
+    degree: (usize, usize),
+    range_u: (f32, f32),
+    range_v: (f32, f32),
+    // Positions for curves and surfaces, `vp` points for parameter curves.
+    control_points: Vec<isize>,
+    knots_u: Vec<f32>,
+    knots_v: Vec<f32>,
+    // Loops of `curv2` statement indexes, one for each `trim` or `hole`.
+    trims: Vec<Vec<(f32, f32, isize)>>,
+    holes: Vec<Vec<(f32, f32, isize)>>,
+    // Assigned once the statement is read.
+    object: usize,
+    material: Option<Cow<'a, str>>,
+}
 
-    // Parse 1 polygon group at the start of the face list. Ignore the polygon group.
-    let (remainder, _) = match polygon_group(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading polygon group")
-    };
+impl<'a> FreeFormIndexed<'a> {
+    fn new(source: CompleteStr<'a>, kind: FreeFormKind, range_u: (f32, f32), range_v: (f32, f32), control_points: Vec<isize>) -> FreeFormIndexed<'a> {
+        FreeFormIndexed {
+            source,
+            kind,
+            basis: Basis::BSpline,
+            rational: false,
+            degree: (0, 0),
+            range_u,
+            range_v,
+            control_points,
+            knots_u: Vec::new(),
+            knots_v: Vec::new(),
+            trims: Vec::new(),
+            holes: Vec::new(),
+            object: 0,
+            material: None,
+        }
+    }
+}
 
-    let (remainder, _) = match smooth_shading(remainder) {
-        Ok(x) => x,
-        Err(_) => panic!("Unable to parse OBJ file: error reading smooth shading")
-    };
+/*
+    Errors
+*/
 
-    let (_, faces) = match face_list(remainder) {
-        Ok(f) => f,
-        Err(_) => panic!("Unable to parse OBJ file: error reading faces")
-    };
+/// The vertex attribute list a face index refers to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexAttribute {
+    Position,
+    TextureCoordinate,
+    Normal,
+    ParameterVertex,
+    ParameterCurve,
+}
 
-    let mut vertices = Vec::new();
-    let mut triangles = Vec::new();
-    for f in faces {
-        for i in 0..3 {
-            let p = vertex_positions[f.vertexes[i] - 1];
-            let uv = match f.texture_coordinates[i] {
-                Some(index) => uvs[index - 1],
-                None => Vector3::zero()
-            };
-            let normal = vertex_normals[f.vertex_normals[i] - 1];
+impl fmt::Display for IndexAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            IndexAttribute::Position => "position",
+            IndexAttribute::TextureCoordinate => "texture coordinate",
+            IndexAttribute::Normal => "normal",
+            IndexAttribute::ParameterVertex => "vp point",
+            IndexAttribute::ParameterCurve => "curv2 curve",
+        };
+        write!(f, "{}", name)
+    }
+}
 
-            let v = Vertex {
-                p,
-                uv,
-                normal,
-            };
-            triangles.push(vertices.len());
-            vertices.push(v);
+/// The stage of OBJ parsing that failed and where it failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjError {
+    MaterialFile(Location),
+    ObjectName(Location),
+    VertexPositions(Location),
+    TextureCoordinates(Location),
+    VertexNormals(Location),
+    Usemtl(Location),
+    PolygonGroup(Location),
+    SmoothShading(Location),
+    Faces(Location),
+    Lines(Location),
+    Points(Location),
+    ParameterVertices(Location),
+    /// A free-form statement could not be read, or appeared where it is not allowed,
+    /// such as `parm` outside a curve or surface, a curve without `cstype` and `deg`, or
+    /// a `cstype` other than `bezier` or `bspline`.
+    FreeForm(Location),
+    UnknownStatement(Location),
+    /// A face referenced an element that does not exist. `count` elements of
+    /// that kind had been defined, so valid indexes are `1..=count` and `-count..=-1`.
+    IndexOutOfRange {
+        location: Location,
+        attribute: IndexAttribute,
+        index: isize,
+        count: usize,
+    },
+}
+
+impl ObjError {
+    pub fn location(&self) -> &Location {
+        match *self {
+            ObjError::MaterialFile(ref l) |
+            ObjError::ObjectName(ref l) |
+            ObjError::VertexPositions(ref l) |
+            ObjError::TextureCoordinates(ref l) |
+            ObjError::VertexNormals(ref l) |
+            ObjError::Usemtl(ref l) |
+            ObjError::PolygonGroup(ref l) |
+            ObjError::SmoothShading(ref l) |
+            ObjError::Faces(ref l) |
+            ObjError::Lines(ref l) |
+            ObjError::Points(ref l) |
+            ObjError::ParameterVertices(ref l) |
+            ObjError::FreeForm(ref l) |
+            ObjError::UnknownStatement(ref l) |
+            ObjError::IndexOutOfRange { location: ref l, .. } => l
         }
     }
+}
 
-    Model {
-        name: obj_name.to_string(),
-        vertices,
-        triangles,
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stage = match *self {
+            ObjError::MaterialFile(_) => "error reading material file".to_string(),
+            ObjError::ObjectName(_) => "error reading object name".to_string(),
+            ObjError::VertexPositions(_) => "error reading vertex positions".to_string(),
+            ObjError::TextureCoordinates(_) => "error reading UV coordinates".to_string(),
+            ObjError::VertexNormals(_) => "error reading vertex normals".to_string(),
+            ObjError::Usemtl(_) => "error reading usemtl".to_string(),
+            ObjError::PolygonGroup(_) => "error reading polygon group".to_string(),
+            ObjError::SmoothShading(_) => "error reading smooth shading".to_string(),
+            ObjError::Faces(_) => "error reading faces".to_string(),
+            ObjError::Lines(_) => "error reading lines".to_string(),
+            ObjError::Points(_) => "error reading points".to_string(),
+            ObjError::ParameterVertices(_) => "error reading parameter space vertices".to_string(),
+            ObjError::FreeForm(_) => "error reading free-form geometry".to_string(),
+            ObjError::UnknownStatement(_) => "unrecognized statement".to_string(),
+            ObjError::IndexOutOfRange { attribute, index, count: 0, .. } => {
+                format!("{} index {} out of range, no {}s defined", attribute, index, attribute)
+            },
+            ObjError::IndexOutOfRange { attribute, index, count, .. } => {
+                format!("{} index {} out of range (valid range 1 to {} or -{} to -1)", attribute, index, count, count)
+            },
+        };
+        write!(f, "Unable to parse OBJ file: {} at {}", stage, self.location())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Error for ObjError {}
 
-    #[test]
-    fn test_parse_name() {
-        let input = CompleteStr("cube\n");
-        let expected_remainder = CompleteStr("\n");
-        let expected_output = CompleteStr("cube");
-        assert_eq!(name(input), Ok((expected_remainder, expected_output)));
+/*
+    OBJ File
+*/
+
+// Converts a 1-based or negative relative OBJ index into a position in a list of `count` items.
+fn resolve_index(index: isize, count: usize) -> Option<usize> {
+    let resolved = if index > 0 {
+        index as usize - 1
+    } else if index < 0 {
+        count.checked_sub(index.unsigned_abs())?
+    } else {
+        return None;
+    };
+
+    if resolved < count {
+        Some(resolved)
+    } else {
+        None
     }
+}
+
+// The part of a `v/vt/vn` corner holding the index of `attribute`.
+fn corner_index(corner: CompleteStr, attribute: IndexAttribute) -> CompleteStr {
+    let skip = match attribute {
+        IndexAttribute::TextureCoordinate => 1,
+        IndexAttribute::Normal => 2,
+        _ => 0
+    };
+    let mut rest = corner.0;
+    for _ in 0..skip {
+        if let Some(i) = rest.find('/') {
+            rest = &rest[i + 1..];
+        }
+    }
+    CompleteStr(rest)
+}
+
+// `source` is the index itself, or the statement holding it.
+fn index_out_of_range(data: &str, source: CompleteStr, index: isize, count: usize, attribute: IndexAttribute) -> ObjError {
+    ObjError::IndexOutOfRange {
+        location: Location::new(data, source),
+        attribute,
+        index,
+        count,
+    }
+}
+
+// Relative indexes refer to the elements defined before the face, so they are made absolute
+// while parsing. Positive indexes are checked once the whole file has been read.
+fn absolute_index(data: &str, source: CompleteStr, index: isize, count: usize, attribute: IndexAttribute) -> Result<isize, ObjError> {
+    if index >= 0 {
+        return Ok(index);
+    }
+
+    match resolve_index(index, count) {
+        Some(i) => Ok(i as isize + 1),
+        None => Err(index_out_of_range(data, source, index, count, attribute))
+    }
+}
+
+fn lookup<T: Copy>(data: &str, source: CompleteStr, list: &[T], index: isize, attribute: IndexAttribute) -> Result<T, ObjError> {
+    match resolve_index(index, list.len()) {
+        Some(i) => Ok(list[i]),
+        None => Err(index_out_of_range(data, source, index, list.len(), attribute))
+    }
+}
+
+/// Options controlling how an OBJ file is turned into a `Model`.
+#[derive(Debug, Clone)]
+pub struct ObjOptions {
+    pub triangulation: Triangulation,
+    /// Give vertices without a `vn` index the normal of their face, averaged with
+    /// neighbouring faces in the same smoothing group. When disabled such vertices get a zero normal.
+    pub generate_normals: bool,
+    /// Tessellate Bezier and B-spline surfaces into triangles, with this many segments along
+    /// each parameter direction, leaving out the triangles outside `trim` loops or inside
+    /// `hole` loops. Surfaces are always kept in `Model::surfaces`.
+    pub tessellation: Option<usize>,
+    /// Fail with `ObjError::UnknownStatement` on statements the parser does not recognize.
+    /// Otherwise they are kept in `unknown_statements` on the result. Loaders apply the
+    /// same setting to material libraries, where unknown statements are skipped.
+    pub strict: bool,
+}
+
+impl Default for ObjOptions {
+    fn default() -> ObjOptions {
+        ObjOptions {
+            triangulation: Triangulation::default(),
+            generate_normals: true,
+            tessellation: None,
+            strict: false,
+        }
+    }
+}
+
+/*
+    Statements
+*/
+
+enum Statement<'a> {
+    Object(Cow<'a, str>),
+    Vertex(Vector3, f32, Option<Vector3>),
+    TextureCoordinates(Vector3),
+    VertexNormal(Vector3),
+    // A face, line or point.
+    Element(FaceIndexed<'a>),
+    Group(Vec<CompleteStr<'a>>),
+    UseMaterial(Cow<'a, str>),
+    MaterialLibrary(Cow<'a, str>),
+    SmoothingGroup(u32),
+    ParameterVertex(Vector3),
+    CurveType(Basis, bool),
+    Degree(usize, usize),
+    // The start of a `curv`, `curv2` or `surf` body.
+    FreeForm(FreeFormIndexed<'a>),
+    Parameter(Direction, Vec<f32>),
+    Trim(Vec<(f32, f32, isize)>),
+    Hole(Vec<(f32, f32, isize)>),
+    End,
+    // A keyword and its arguments.
+    Unknown(CompleteStr<'a>, Cow<'a, str>),
+}
+
+fn parse_statement<'a, O>(
+    data: &str,
+    input: CompleteStr<'a>,
+    parser: fn(CompleteStr<'a>) -> IResult<CompleteStr<'a>, O>,
+    error: fn(Location) -> ObjError
+) -> Result<(CompleteStr<'a>, O), ObjError> {
+    parser(input).map_err(|e| error(error_location(data, &e, input)))
+}
+
+// Parses the statement at the start of `input`, choosing the parser from its keyword.
+fn statement<'a>(data: &str, input: CompleteStr<'a>, strict: bool) -> Result<(CompleteStr<'a>, Statement<'a>), ObjError> {
+    let keyword = match keyword(input) {
+        Ok((_, keyword)) => keyword,
+        Err(e) => return Err(ObjError::UnknownStatement(error_location(data, &e, input)))
+    };
+
+    let result = match keyword.0 {
+        "mtllib" => {
+            let (remainder, names) = parse_statement(data, input, material_file, ObjError::MaterialFile)?;
+            (remainder, Statement::MaterialLibrary(names))
+        },
+        "o" => {
+            let (remainder, name) = parse_statement(data, input, object_name, ObjError::ObjectName)?;
+            (remainder, Statement::Object(name))
+        },
+        "v" => {
+            let (remainder, (v, w, color)) = parse_statement(data, input, vertex, ObjError::VertexPositions)?;
+            (remainder, Statement::Vertex(v, w, color))
+        },
+        "vt" => {
+            let (remainder, uv) = parse_statement(data, input, texture_coordinates, ObjError::TextureCoordinates)?;
+            (remainder, Statement::TextureCoordinates(uv))
+        },
+        "vn" => {
+            let (remainder, vn) = parse_statement(data, input, vertex_normal, ObjError::VertexNormals)?;
+            (remainder, Statement::VertexNormal(vn))
+        },
+        "usemtl" => {
+            let (remainder, name) = parse_statement(data, input, usemtl, ObjError::Usemtl)?;
+            (remainder, Statement::UseMaterial(name))
+        },
+        "g" => {
+            let (remainder, names) = parse_statement(data, input, polygon_group, ObjError::PolygonGroup)?;
+            (remainder, Statement::Group(names))
+        },
+        "s" => {
+            let (remainder, group) = parse_statement(data, input, smoothing_group, ObjError::SmoothShading)?;
+            (remainder, Statement::SmoothingGroup(group))
+        },
+        "f" => {
+            let (remainder, f) = parse_statement(data, input, face, ObjError::Faces)?;
+            (remainder, Statement::Element(f))
+        },
+        "l" => {
+            let (remainder, l) = parse_statement(data, input, line, ObjError::Lines)?;
+            (remainder, Statement::Element(l))
+        },
+        "p" => {
+            let (remainder, p) = parse_statement(data, input, point, ObjError::Points)?;
+            (remainder, Statement::Element(p))
+        },
+        "vp" => {
+            let (remainder, vp) = parse_statement(data, input, parameter_vertex, ObjError::ParameterVertices)?;
+            (remainder, Statement::ParameterVertex(vp))
+        },
+        "cstype" => {
+            let (remainder, (basis, rational)) = parse_statement(data, input, curve_type, ObjError::FreeForm)?;
+            (remainder, Statement::CurveType(basis, rational))
+        },
+        "deg" => {
+            let (remainder, (u, v)) = parse_statement(data, input, degree, ObjError::FreeForm)?;
+            (remainder, Statement::Degree(u, v))
+        },
+        "curv" => {
+            let (remainder, c) = parse_statement(data, input, curve, ObjError::FreeForm)?;
+            (remainder, Statement::FreeForm(c))
+        },
+        "curv2" => {
+            let (remainder, c) = parse_statement(data, input, parameter_curve, ObjError::FreeForm)?;
+            (remainder, Statement::FreeForm(c))
+        },
+        "surf" => {
+            let (remainder, s) = parse_statement(data, input, surface, ObjError::FreeForm)?;
+            (remainder, Statement::FreeForm(s))
+        },
+        "parm" => {
+            let (remainder, (direction, values)) = parse_statement(data, input, parameter_values, ObjError::FreeForm)?;
+            (remainder, Statement::Parameter(direction, values))
+        },
+        "trim" => {
+            let (remainder, curves) = parse_statement(data, input, trim, ObjError::FreeForm)?;
+            (remainder, Statement::Trim(curves))
+        },
+        "hole" => {
+            let (remainder, curves) = parse_statement(data, input, hole, ObjError::FreeForm)?;
+            (remainder, Statement::Hole(curves))
+        },
+        "end" => {
+            let (remainder, _) = parse_statement(data, input, end, ObjError::FreeForm)?;
+            (remainder, Statement::End)
+        },
+        _ if strict => {
+            return Err(ObjError::UnknownStatement(Location::new(data, statement_start(input))));
+        },
+        _ => {
+            let (remainder, (keyword, arguments)) = parse_statement(data, input, unknown_statement, ObjError::UnknownStatement)?;
+            (remainder, Statement::Unknown(keyword, arguments))
+        }
+    };
+
+    Ok(result)
+}
+
+/*
+    OBJ File
+*/
+
+struct ObjectData<'a> {
+    // `None` for faces that appear before the first `o` statement.
+    name: Option<Cow<'a, str>>,
+    first_face: usize,
+}
+
+struct ObjData<'a> {
+    positions: Vec<Vector3>,
+    // The weight and color of each position.
+    weights: Vec<f32>,
+    colors: Vec<Option<Vector3>>,
+    uvs: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    faces: Vec<FaceIndexed<'a>>,
+    objects: Vec<ObjectData<'a>>,
+    // The sets of group names selected by `g` statements. Faces that appear before
+    // any `g` are in the first set, the default group.
+    group_sets: Vec<Vec<CompleteStr<'a>>>,
+    material: Option<Cow<'a, str>>,
+    material_libraries: Vec<Cow<'a, str>>,
+    smoothing_group: u32,
+    parameter_vertices: Vec<Vector3>,
+    curve_type: Option<(Basis, bool)>,
+    degree: Option<(usize, usize)>,
+    // The curve or surface whose body is being read, until its `end`.
+    free_form: Option<FreeFormIndexed<'a>>,
+    curves: Vec<FreeFormIndexed<'a>>,
+    parameter_curves: Vec<FreeFormIndexed<'a>>,
+    surfaces: Vec<FreeFormIndexed<'a>>,
+    // The start, keyword and arguments of each unrecognized statement.
+    unknown_statements: Vec<(CompleteStr<'a>, CompleteStr<'a>, Cow<'a, str>)>,
+}
+
+impl<'a> ObjData<'a> {
+    fn object_has_free_form(&self, object: usize) -> bool {
+        self.curves.iter().chain(self.surfaces.iter()).any(|c| c.object == object)
+    }
+
+    fn object_faces(&self, object: usize) -> &[FaceIndexed<'a>] {
+        let end = match self.objects.get(object + 1) {
+            Some(next) => next.first_face,
+            None => self.faces.len()
+        };
+        &self.faces[self.objects[object].first_face..end]
+    }
+}
+
+// Adds a `trim` or `hole` statement to the surface being read as one loop.
+fn add_trim_loop<'a>(data: &str, obj: &mut ObjData<'a>, source: CompleteStr<'a>, mut curves: Vec<(f32, f32, isize)>, hole: bool) -> Result<(), ObjError> {
+    for c in &mut curves {
+        c.2 = absolute_index(data, source, c.2, obj.parameter_curves.len(), IndexAttribute::ParameterCurve)?;
+    }
+
+    match obj.free_form {
+        Some(ref mut e) if e.kind == FreeFormKind::Surface => {
+            if hole {
+                e.holes.push(curves);
+            } else {
+                e.trims.push(curves);
+            }
+            Ok(())
+        },
+        _ => Err(ObjError::FreeForm(Location::new(data, source)))
+    }
+}
+
+// Reads every statement in the file in order, keeping the elements each one defines.
+fn parse_statements<'a>(data: &'a str, options: &ObjOptions) -> Result<ObjData<'a>, ObjError> {
+    let mut obj = ObjData {
+        positions: Vec::new(),
+        weights: Vec::new(),
+        colors: Vec::new(),
+        uvs: Vec::new(),
+        normals: Vec::new(),
+        faces: Vec::new(),
+        objects: vec![ObjectData { name: None, first_face: 0 }],
+        group_sets: vec![vec![CompleteStr(DEFAULT_GROUP_NAME)]],
+        material: None,
+        material_libraries: Vec::new(),
+        smoothing_group: 0,
+        parameter_vertices: Vec::new(),
+        curve_type: None,
+        degree: None,
+        free_form: None,
+        curves: Vec::new(),
+        parameter_curves: Vec::new(),
+        surfaces: Vec::new(),
+        unknown_statements: Vec::new(),
+    };
+
+    let mut remainder = CompleteStr(data);
+    loop {
+        if let Ok((r, _)) = ignore_lines(remainder) {
+            remainder = r;
+        }
+        if remainder.is_empty() {
+            break;
+        }
+
+        let (r, statement) = match statement(data, remainder, options.strict) {
+            Ok(x) => x,
+            // A final comment or whitespace without a line ending.
+            Err(_) if only_ignored_lines(remainder) => break,
+            Err(e) => return Err(e)
+        };
+        let source = statement_start(remainder);
+        remainder = r;
+
+        match statement {
+            Statement::Object(name) => {
+                let object = ObjectData {
+                    name: Some(name),
+                    first_face: obj.faces.len(),
+                };
+                if obj.objects.len() == 1 && obj.objects[0].name.is_none() && obj.faces.is_empty() && !obj.object_has_free_form(0) {
+                    obj.objects[0] = object;
+                } else {
+                    obj.objects.push(object);
+                }
+            },
+            Statement::Vertex(v, w, color) => {
+                obj.positions.push(v);
+                obj.weights.push(w);
+                obj.colors.push(color);
+            },
+            Statement::TextureCoordinates(uv) => obj.uvs.push(uv),
+            Statement::VertexNormal(vn) => obj.normals.push(vn),
+            Statement::Element(mut f) => {
+                for i in 0..f.vertexes.len() {
+                    let corner = f.corners[i];
+                    f.vertexes[i] = absolute_index(data, corner_index(corner, IndexAttribute::Position), f.vertexes[i], obj.positions.len(), IndexAttribute::Position)?;
+                    if let Some(index) = f.texture_coordinates[i] {
+                        let source = corner_index(corner, IndexAttribute::TextureCoordinate);
+                        f.texture_coordinates[i] = Some(absolute_index(data, source, index, obj.uvs.len(), IndexAttribute::TextureCoordinate)?);
+                    }
+                    if let Some(index) = f.vertex_normals[i] {
+                        let source = corner_index(corner, IndexAttribute::Normal);
+                        f.vertex_normals[i] = Some(absolute_index(data, source, index, obj.normals.len(), IndexAttribute::Normal)?);
+                    }
+                }
+                f.groups = obj.group_sets.len() - 1;
+                f.material = obj.material.clone();
+                f.smoothing_group = obj.smoothing_group;
+                obj.faces.push(f);
+            },
+            Statement::Group(names) => obj.group_sets.push(names),
+            Statement::UseMaterial(name) => obj.material = Some(name),
+            Statement::MaterialLibrary(names) => obj.material_libraries.push(names),
+            Statement::SmoothingGroup(group) => obj.smoothing_group = group,
+            Statement::ParameterVertex(vp) => obj.parameter_vertices.push(vp),
+            Statement::CurveType(basis, rational) => obj.curve_type = Some((basis, rational)),
+            Statement::Degree(u, v) => obj.degree = Some((u, v)),
+            Statement::FreeForm(mut e) => {
+                let ((basis, rational), degree) = match (&obj.free_form, obj.curve_type, obj.degree) {
+                    (None, Some(curve_type), Some(degree)) => (curve_type, degree),
+                    _ => return Err(ObjError::FreeForm(Location::new(data, source)))
+                };
+                let (count, attribute) = match e.kind {
+                    FreeFormKind::ParameterCurve => (obj.parameter_vertices.len(), IndexAttribute::ParameterVertex),
+                    _ => (obj.positions.len(), IndexAttribute::Position)
+                };
+                for i in 0..e.control_points.len() {
+                    e.control_points[i] = absolute_index(data, e.source, e.control_points[i], count, attribute)?;
+                }
+                e.basis = basis;
+                e.rational = rational;
+                e.degree = degree;
+                e.object = obj.objects.len() - 1;
+                e.material = obj.material.clone();
+                obj.free_form = Some(e);
+            },
+            Statement::Parameter(direction, values) => {
+                match obj.free_form {
+                    Some(ref mut e) if direction == Direction::U => e.knots_u.extend(values),
+                    Some(ref mut e) if e.kind == FreeFormKind::Surface => e.knots_v.extend(values),
+                    _ => return Err(ObjError::FreeForm(Location::new(data, source)))
+                }
+            },
+            Statement::Trim(curves) => add_trim_loop(data, &mut obj, source, curves, false)?,
+            Statement::Hole(curves) => add_trim_loop(data, &mut obj, source, curves, true)?,
+            Statement::End => {
+                match obj.free_form.take() {
+                    Some(e) => match e.kind {
+                        FreeFormKind::Curve => obj.curves.push(e),
+                        FreeFormKind::ParameterCurve => obj.parameter_curves.push(e),
+                        FreeFormKind::Surface => obj.surfaces.push(e),
+                    },
+                    None => return Err(ObjError::FreeForm(Location::new(data, source)))
+                }
+            },
+            Statement::Unknown(keyword, arguments) => obj.unknown_statements.push((source, keyword, arguments)),
+        }
+    }
+
+    // A curve or surface without an `end`.
+    if let Some(e) = obj.free_form {
+        return Err(ObjError::FreeForm(Location::new(data, e.source)));
+    }
+
+    Ok(obj)
+}
+
+// A vertex without a `vn` index, waiting for a normal.
+struct GeneratedNormal {
+    vertex: usize,
+    position: isize,
+    smoothing_group: u32,
+    face_normal: Vector3,
+}
+
+// Vertices outside any smoothing group take the normal of their face. Within a group,
+// vertices at the same position share the average normal of the group's faces around
+// it, so edges inside a group are smooth and edges between groups stay hard.
+fn apply_generated_normals(vertices: &mut [Vertex], normals: &[GeneratedNormal]) {
+    let mut sums: HashMap<(u32, isize), (f32, f32, f32)> = HashMap::new();
+    for n in normals.iter().filter(|n| n.smoothing_group != 0) {
+        let sum = sums.entry((n.smoothing_group, n.position)).or_insert((0.0, 0.0, 0.0));
+        sum.0 += n.face_normal.x;
+        sum.1 += n.face_normal.y;
+        sum.2 += n.face_normal.z;
+    }
+
+    for n in normals {
+        vertices[n.vertex].normal = match sums.get(&(n.smoothing_group, n.position)) {
+            Some(&(x, y, z)) if n.smoothing_group != 0 => {
+                let length = (x * x + y * y + z * z).sqrt();
+                if length > 0.0 {
+                    Vector3::new(x / length, y / length, z / length)
+                } else {
+                    n.face_normal
+                }
+            },
+            _ => n.face_normal
+        };
+    }
+}
+
+fn control_points(data: &str, obj: &ObjData, e: &FreeFormIndexed) -> Result<Vec<ControlPoint>, ObjError> {
+    e.control_points.iter().map(|&i| {
+        Ok(ControlPoint {
+            p: lookup(data, e.source, &obj.positions, i, IndexAttribute::Position)?,
+            w: lookup(data, e.source, &obj.weights, i, IndexAttribute::Position)?,
+        })
+    }).collect()
+}
+
+fn build_curve(data: &str, obj: &ObjData, e: &FreeFormIndexed) -> Result<Curve, ObjError> {
+    Ok(Curve {
+        basis: e.basis,
+        rational: e.rational,
+        degree: e.degree.0,
+        start: e.range_u.0,
+        end: e.range_u.1,
+        control_points: control_points(data, obj, e)?,
+        knots: e.knots_u.clone(),
+    })
+}
+
+fn build_trim_curves(data: &str, obj: &ObjData, e: &FreeFormIndexed, curves: &[(f32, f32, isize)]) -> Result<Vec<TrimCurve>, ObjError> {
+    curves.iter().map(|&(start, end, index)| {
+        let c = match resolve_index(index, obj.parameter_curves.len()) {
+            Some(i) => &obj.parameter_curves[i],
+            None => return Err(index_out_of_range(data, e.source, index, obj.parameter_curves.len(), IndexAttribute::ParameterCurve))
+        };
+        let control_points = c.control_points.iter()
+            .map(|&i| lookup(data, c.source, &obj.parameter_vertices, i, IndexAttribute::ParameterVertex))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(TrimCurve {
+            start,
+            end,
+            curve: ParameterCurve {
+                basis: c.basis,
+                rational: c.rational,
+                degree: c.degree.0,
+                control_points,
+                knots: c.knots_u.clone(),
+            },
+        })
+    }).collect()
+}
+
+fn build_surface(data: &str, obj: &ObjData, e: &FreeFormIndexed) -> Result<Surface, ObjError> {
+    Ok(Surface {
+        basis: e.basis,
+        rational: e.rational,
+        degree_u: e.degree.0,
+        degree_v: e.degree.1,
+        start_u: e.range_u.0,
+        end_u: e.range_u.1,
+        start_v: e.range_v.0,
+        end_v: e.range_v.1,
+        control_points: control_points(data, obj, e)?,
+        knots_u: e.knots_u.clone(),
+        knots_v: e.knots_v.clone(),
+        trims: e.trims.iter().map(|l| build_trim_curves(data, obj, e, l)).collect::<Result<_, _>>()?,
+        holes: e.holes.iter().map(|l| build_trim_curves(data, obj, e, l)).collect::<Result<_, _>>()?,
+        material: e.material.as_ref().map(|m| m.to_string()),
+    })
+}
+
+// Extends the last submesh if it uses the same material.
+fn add_to_submeshes(submeshes: &mut Vec<Submesh>, material: Option<String>, range: Range<usize>) {
+    match submeshes.last_mut() {
+        Some(ref mut submesh) if submesh.material == material => submesh.range.end = range.end,
+        _ => submeshes.push(Submesh {
+            material,
+            material_index: None,
+            range,
+        })
+    }
+}
+
+// Builds a model from a run of faces and the free-form geometry of `objects`.
+// The vertex pools are shared by every object in the file.
+fn build_model(data: &str, obj: &ObjData, name: &str, faces: &[FaceIndexed], objects: Range<usize>, options: &ObjOptions) -> Result<Model, ObjError> {
+    let mut vertices = Vec::new();
+    let mut triangles = Vec::new();
+    let mut groups: Vec<Group> = Vec::new();
+    let mut submeshes: Vec<Submesh> = Vec::new();
+    let mut lines = Vec::new();
+    let mut points = Vec::new();
+    let mut smoothing_groups = Vec::new();
+    let mut generated_normals = Vec::new();
+    for f in faces {
+        let first = vertices.len();
+        for i in 0..f.vertexes.len() {
+            let corner = f.corners[i];
+            let source = corner_index(corner, IndexAttribute::Position);
+            let p = lookup(data, source, &obj.positions, f.vertexes[i], IndexAttribute::Position)?;
+            let w = lookup(data, source, &obj.weights, f.vertexes[i], IndexAttribute::Position)?;
+            let color = lookup(data, source, &obj.colors, f.vertexes[i], IndexAttribute::Position)?;
+            let uv = match f.texture_coordinates[i] {
+                Some(index) => lookup(data, corner_index(corner, IndexAttribute::TextureCoordinate), &obj.uvs, index, IndexAttribute::TextureCoordinate)?,
+                None => Vector3::zero()
+            };
+            let normal = match f.vertex_normals[i] {
+                Some(index) => Some(lookup(data, corner_index(corner, IndexAttribute::Normal), &obj.normals, index, IndexAttribute::Normal)?),
+                None => None
+            };
+
+            let v = Vertex {
+                p,
+                w,
+                uv,
+                normal: normal.unwrap_or_else(Vector3::zero),
+                color,
+            };
+            vertices.push(v);
+        }
+
+        // Lines and points are kept apart from the triangles, outside groups and submeshes.
+        match f.kind {
+            ElementKind::Line => {
+                lines.extend((first..vertices.len() - 1).map(|i| [i, i + 1]));
+                continue;
+            },
+            ElementKind::Point => {
+                points.extend(first..vertices.len());
+                continue;
+            },
+            ElementKind::Face => ()
+        }
+
+        let polygon: Vec<Vector3> = vertices[first..].iter().map(|v| v.p).collect();
+        let first_triangle = triangles.len();
+        for t in triangulate(&polygon, options.triangulation) {
+            triangles.extend(t.iter().map(|i| first + i));
+        }
+
+        for name in &obj.group_sets[f.groups] {
+            let group = match groups.iter().position(|g| g.name == name.0) {
+                Some(i) => &mut groups[i],
+                None => {
+                    groups.push(Group {
+                        name: name.to_string(),
+                        ranges: Vec::new(),
+                    });
+                    groups.last_mut().unwrap()
+                }
+            };
+            group.add_triangles(first_triangle..triangles.len());
+        }
+
+        add_to_submeshes(&mut submeshes, f.material.as_ref().map(|m| m.to_string()), first_triangle..triangles.len());
+
+        smoothing_groups.resize(triangles.len() / 3, f.smoothing_group);
+
+        if options.generate_normals && f.vertex_normals.iter().any(Option::is_none) {
+            let normal = face_normal(&polygon);
+            for (i, vn) in f.vertex_normals.iter().enumerate() {
+                if vn.is_none() {
+                    generated_normals.push(GeneratedNormal {
+                        vertex: first + i,
+                        position: f.vertexes[i],
+                        smoothing_group: f.smoothing_group,
+                        face_normal: normal,
+                    });
+                }
+            }
+        }
+    }
+    apply_generated_normals(&mut vertices, &generated_normals);
+
+    let curves = obj.curves.iter()
+        .filter(|c| objects.contains(&c.object))
+        .map(|c| build_curve(data, obj, c))
+        .collect::<Result<Vec<_>, _>>()?;
+    let surfaces = obj.surfaces.iter()
+        .filter(|s| objects.contains(&s.object))
+        .map(|s| build_surface(data, obj, s))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(resolution) = options.tessellation {
+        for surface in &surfaces {
+            if let Some((surface_points, surface_triangles)) = surface.tessellate(resolution) {
+                let first = vertices.len();
+                let first_triangle = triangles.len();
+                vertices.extend(surface_points.into_iter().map(|p| Vertex {
+                    p: p.p,
+                    w: 1.0,
+                    uv: p.uv,
+                    normal: p.normal,
+                    color: None,
+                }));
+                for t in surface_triangles {
+                    triangles.extend(t.iter().map(|i| first + i));
+                }
+                smoothing_groups.resize(triangles.len() / 3, 0);
+                add_to_submeshes(&mut submeshes, surface.material.clone(), first_triangle..triangles.len());
+            }
+        }
+    }
+
+    Ok(Model {
+        name: name.to_string(),
+        vertices,
+        triangles,
+        lines,
+        points,
+        smoothing_groups,
+        groups,
+        submeshes,
+        material_libraries: obj.material_libraries.iter().map(|l| l.to_string()).collect(),
+        materials: Vec::new(),
+        curves,
+        surfaces,
+        unknown_statements: Vec::new(),
+    })
+}
+
+// Line numbers are counted in one pass, as files with vendor extensions can have many of them.
+fn unknown_statements(data: &str, obj: &ObjData) -> Vec<UnknownStatement> {
+    let mut line = 1;
+    let mut counted = 0;
+    obj.unknown_statements.iter().map(|&(source, keyword, ref arguments)| {
+        let offset = data.len() - source.len();
+        line += data[counted..offset].matches('\n').count();
+        counted = offset;
+
+        UnknownStatement {
+            line,
+            keyword: keyword.to_string(),
+            arguments: arguments.to_string(),
+        }
+    }).collect()
+}
+
+const DEFAULT_OBJECT_NAME: &str = "Object";
+const DEFAULT_GROUP_NAME: &str = "default";
+
+/// Parses an OBJ file into a single model containing the faces of every object in the file.
+/// The model is named after the first object.
+pub fn parse_obj_file(data: &str) -> Result<Model, ObjError> {
+    parse_obj_file_with_options(data, &ObjOptions::default())
+}
+
+pub fn parse_obj_file_with_options(data: &str, options: &ObjOptions) -> Result<Model, ObjError> {
+    let obj = parse_statements(data, options)?;
+    let name = obj.objects.iter()
+        .find_map(|o| o.name.as_deref())
+        .unwrap_or(DEFAULT_OBJECT_NAME);
+
+    let mut model = build_model(data, &obj, name, &obj.faces, 0..obj.objects.len(), options)?;
+    model.unknown_statements = unknown_statements(data, &obj);
+    Ok(model)
+}
+
+/// Parses an OBJ file into one model per object.
+pub fn parse_obj_scene(data: &str) -> Result<Scene, ObjError> {
+    parse_obj_scene_with_options(data, &ObjOptions::default())
+}
+
+pub fn parse_obj_scene_with_options(data: &str, options: &ObjOptions) -> Result<Scene, ObjError> {
+    let obj = parse_statements(data, options)?;
+
+    let mut models = Vec::new();
+    for (i, object) in obj.objects.iter().enumerate() {
+        let faces = obj.object_faces(i);
+        // Faces before the first `o` only form an object if there are any.
+        if object.name.is_none() && faces.is_empty() && !obj.object_has_free_form(i) {
+            continue;
+        }
+
+        let name = object.name.as_deref().unwrap_or(DEFAULT_OBJECT_NAME);
+        models.push(build_model(data, &obj, name, faces, i..i + 1, options)?);
+    }
+
+    Ok(Scene {
+        models,
+        unknown_statements: unknown_statements(data, &obj),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
     fn test_parse_object_name() {
         let input = CompleteStr("o cube\n");
         let expected_remainder = CompleteStr("");
-        let expected_output = Some(CompleteStr("cube"));
+        let expected_output = Cow::Borrowed("cube");
         assert_eq!(object_name(input), Ok((expected_remainder, expected_output)));
     }
 
@@ -432,11 +1413,13 @@ mod tests {
         let expected_remainder = CompleteStr("");
 
         match vertex(input) {
-            Ok((remainder, v)) => {
+            Ok((remainder, (v, w, c))) => {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(v.x, 1.0);
                 assert_eq!(v.y, 1.0);
                 assert_eq!(v.z, -1.0);
+                assert_eq!(w, 1.0);
+                assert!(c.is_none());
             },
             Err(err) => panic!(err)
         }
@@ -448,11 +1431,13 @@ mod tests {
         let expected_remainder = CompleteStr("");
 
         match vertex(input) {
-            Ok((remainder, v)) => {
+            Ok((remainder, (v, w, c))) => {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(v.x, 1.0);
                 assert_eq!(v.y, 1.0);
                 assert_eq!(v.z, -1.0);
+                assert_eq!(w, 1.0);
+                assert!(c.is_none());
             },
             Err(err) => panic!(err)
         }
@@ -464,11 +1449,13 @@ mod tests {
         let expected_remainder = CompleteStr("");
 
         match vertex(input) {
-            Ok((remainder, v)) => {
+            Ok((remainder, (v, w, c))) => {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(v.x, 1.0);
                 assert_eq!(v.y, 1.0);
                 assert_eq!(v.z, -1.0);
+                assert_eq!(w, 1.0);
+                assert!(c.is_none());
             },
             Err(err) => panic!(err)
         }
@@ -480,11 +1467,13 @@ mod tests {
         let expected_remainder = CompleteStr("");
 
         match vertex(input) {
-            Ok((remainder, v)) => {
+            Ok((remainder, (v, w, c))) => {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(v.x, 1.0);
                 assert_eq!(v.y, 1.0);
                 assert_eq!(v.z, -1.0);
+                assert_eq!(w, 1.0);
+                assert!(c.is_none());
             },
             Err(err) => panic!(err)
         }
@@ -496,135 +1485,18 @@ mod tests {
         let expected_remainder = CompleteStr("");
 
         match vertex(input) {
-            Ok((remainder, v)) => {
+            Ok((remainder, (v, w, c))) => {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(v.x, 1.0);
                 assert_eq!(v.y, 1.0);
                 assert_eq!(v.z, -1.0);
+                assert_eq!(w, 1.0);
+                assert!(c.is_none());
             },
             Err(err) => panic!(err)
         }
     }
 
-    #[test]
-    fn test_parse_vertex_list() {
-        let input = CompleteStr("v 1.000000 1.000000 -1.000000\nv 1.000000 -1.000000 -1.000000\nv 1.000000 1.000000 1.000000\n");
-        let expected_remainder = CompleteStr("");
-
-        match vertex_list(input) {
-            Ok((remainder, vertices)) => {
-                assert_eq!(remainder, expected_remainder);
-                assert_eq!(vertices.len(), 3);
-                assert_eq!(vertices[0].x, 1.0);
-                assert_eq!(vertices[0].y, 1.0);
-                assert_eq!(vertices[0].z, -1.0);
-                assert_eq!(vertices[1].x, 1.0);
-                assert_eq!(vertices[1].y, -1.0);
-                assert_eq!(vertices[1].z, -1.0);
-                assert_eq!(vertices[2].x, 1.0);
-                assert_eq!(vertices[2].y, 1.0);
-                assert_eq!(vertices[2].z, 1.0);
-            },
-            Err(err) => panic!(err)
-        }
-    }
-
-    #[test]
-    fn test_parse_vertex_list_crlf() {
-        let input = CompleteStr("v 1.000000 1.000000 -1.000000\r\nv 1.000000 -1.000000 -1.000000\r\nv 1.000000 1.000000 1.000000\r\n");
-        let expected_remainder = CompleteStr("");
-
-        match vertex_list(input) {
-            Ok((remainder, vertices)) => {
-                assert_eq!(remainder, expected_remainder);
-                assert_eq!(vertices.len(), 3);
-                assert_eq!(vertices[0].x, 1.0);
-                assert_eq!(vertices[0].y, 1.0);
-                assert_eq!(vertices[0].z, -1.0);
-                assert_eq!(vertices[1].x, 1.0);
-                assert_eq!(vertices[1].y, -1.0);
-                assert_eq!(vertices[1].z, -1.0);
-                assert_eq!(vertices[2].x, 1.0);
-                assert_eq!(vertices[2].y, 1.0);
-                assert_eq!(vertices[2].z, 1.0);
-            },
-            Err(err) => panic!(err)
-        }
-    }
-
-    #[test]
-    fn test_parse_vertex_list_with_following_texture_coordinates() {
-        let input = CompleteStr("v 1.000000 1.000000 -1.000000\nv 1.000000 -1.000000 -1.000000\nv 1.000000 1.000000 1.000000\nvt 0.333134 0.000200\n");
-        let expected_remainder = CompleteStr("vt 0.333134 0.000200\n");
-
-        match vertex_list(input) {
-            Ok((remainder, vertices)) => {
-                assert_eq!(remainder, expected_remainder);
-                assert_eq!(vertices.len(), 3);
-                assert_eq!(vertices[0].x, 1.0);
-                assert_eq!(vertices[0].y, 1.0);
-                assert_eq!(vertices[0].z, -1.0);
-                assert_eq!(vertices[1].x, 1.0);
-                assert_eq!(vertices[1].y, -1.0);
-                assert_eq!(vertices[1].z, -1.0);
-                assert_eq!(vertices[2].x, 1.0);
-                assert_eq!(vertices[2].y, 1.0);
-                assert_eq!(vertices[2].z, 1.0);
-            },
-            Err(err) => panic!(err)
-        }
-    }
-
-    #[test]
-    fn test_parse_vertex_list_with_following_texture_coordinates_crlf() {
-        let input = CompleteStr("v 1.000000 1.000000 -1.000000\r\nv 1.000000 -1.000000 -1.000000\r\nv 1.000000 1.000000 1.000000\r\nvt 0.333134 0.000200\r\n");
-        let expected_remainder = CompleteStr("vt 0.333134 0.000200\r\n");
-
-        match vertex_list(input) {
-            Ok((remainder, vertices)) => {
-                assert_eq!(remainder, expected_remainder);
-                assert_eq!(vertices.len(), 3);
-                assert_eq!(vertices[0].x, 1.0);
-                assert_eq!(vertices[0].y, 1.0);
-                assert_eq!(vertices[0].z, -1.0);
-                assert_eq!(vertices[1].x, 1.0);
-                assert_eq!(vertices[1].y, -1.0);
-                assert_eq!(vertices[1].z, -1.0);
-                assert_eq!(vertices[2].x, 1.0);
-                assert_eq!(vertices[2].y, 1.0);
-                assert_eq!(vertices[2].z, 1.0);
-            },
-            Err(err) => panic!(err)
-        }
-    }
-
-    #[test]
-    fn test_parse_comment() {
-        let input = CompleteStr("#this is a comment\n");
-        let expected_remainder = CompleteStr("");
-        let expected_output = CompleteStr("this is a comment");
-        
-        assert_eq!(comment(input), Ok((expected_remainder, expected_output)));
-    }
-
-    #[test]
-    fn test_parse_comment_crlf() {
-        let input = CompleteStr("#this is a comment\r\nNext Line");
-        let expected_remainder = CompleteStr("Next Line");
-        let expected_output = CompleteStr("this is a comment");
-        
-        assert_eq!(comment(input), Ok((expected_remainder, expected_output)));
-    }
-
-    #[test]
-    fn test_parse_spaces() {
-        let input = CompleteStr("   spaces  ");
-        let expected_remainder = CompleteStr("spaces  ");
-        let expected_output = CompleteStr("   ");
-
-        assert_eq!(spaces(input), Ok((expected_remainder, expected_output)));
-    }
-
     #[test]
     fn test_parse_texture_coordinates() {
         let input = CompleteStr("vt 0.333134 0.000200\n");
@@ -690,35 +1562,12 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_parse_vertex_normal_list() {
-        let input = CompleteStr("vn 0.0000 1.0000 0.0000\nvn 0.0000 0.0000 1.0000\nvn -1.0000 0.0000 0.0000\n");
-        let expected_remainder = CompleteStr("");
-
-        match vertex_normal_list(input) {
-            Ok((remainder, vertex_normals)) => {
-                assert_eq!(remainder, expected_remainder);
-                assert_eq!(vertex_normals.len(), 3);
-                assert_eq!(vertex_normals[0].x, 0.0);
-                assert_eq!(vertex_normals[0].y, 1.0);
-                assert_eq!(vertex_normals[0].z, 0.0);
-                assert_eq!(vertex_normals[1].x, 0.0);
-                assert_eq!(vertex_normals[1].y, 0.0);
-                assert_eq!(vertex_normals[1].z, 1.0);
-                assert_eq!(vertex_normals[2].x, -1.0);
-                assert_eq!(vertex_normals[2].y, 0.0);
-                assert_eq!(vertex_normals[2].z, 0.0);
-            },
-            Err(err) => panic!(err)
-        }
-    }
-
     #[test]
     fn test_parse_face_index() {
         let input = CompleteStr("1/16/10005 ");
         let expected_remainder = CompleteStr(" ");
 
-        assert_eq!(face_index(input), Ok((expected_remainder, (1, Some(16), 10005))));
+        assert_eq!(face_index(input), Ok((expected_remainder, (1, Some(16), Some(10005)))));
     }
 
     #[test]
@@ -731,7 +1580,7 @@ mod tests {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(face.vertexes, [5, 3, 1]);
                 assert_eq!(face.texture_coordinates, [Some(1), Some(2), Some(3)]);
-                assert_eq!(face.vertex_normals, [1, 1, 1]);
+                assert_eq!(face.vertex_normals, [Some(1), Some(1), Some(1)]);
             },
             Err(err) => panic!(err)
         }
@@ -747,7 +1596,7 @@ mod tests {
                 assert_eq!(remainder, expected_remainder);
                 assert_eq!(face.vertexes, [5, 3, 1]);
                 assert_eq!(face.texture_coordinates, [None, None, None]);
-                assert_eq!(face.vertex_normals, [1, 1, 1]);
+                assert_eq!(face.vertex_normals, [Some(1), Some(1), Some(1)]);
             },
             Err(err) => panic!(err)
         }
@@ -757,7 +1606,7 @@ mod tests {
     fn test_parse_usemtl() {
         let input = CompleteStr("usemtl Material\n");
         let expected_remainder = CompleteStr("");
-        let expected_output = Some(CompleteStr("Material"));
+        let expected_output = Cow::Borrowed("Material");
 
         assert_eq!(usemtl(input), Ok((expected_remainder, expected_output)));
     }
@@ -766,35 +1615,76 @@ mod tests {
     fn test_parse_material_file() {
         let input = CompleteStr("mtllib cube_uv.mtl\n");
         let expected_remainder = CompleteStr("");
-        let expected_output = Some(CompleteStr("cube_uv.mtl"));
+        let expected_output = Cow::Borrowed("cube_uv.mtl");
 
         assert_eq!(material_file(input), Ok((expected_remainder, expected_output)))
     }
 
     #[test]
-    fn test_parse_smooth_shading() {
-        let input = CompleteStr("s off\n");
+    fn test_parse_material_file_multiple() {
+        let input = CompleteStr("mtllib common.mtl materials/cube uv.mtl # libraries\n");
         let expected_remainder = CompleteStr("");
+        let expected_output = Cow::Borrowed("common.mtl materials/cube uv.mtl");
+
+        assert_eq!(material_file(input), Ok((expected_remainder, expected_output)))
+    }
 
-        assert_eq!(smooth_shading(input), Ok((expected_remainder, Some(false))));
+    #[test]
+    fn test_parse_smoothing_group() {
+        let input = CompleteStr("s off\n");
+        let expected_remainder = CompleteStr("");
 
-        let input = CompleteStr("s on\n");
-        assert_eq!(smooth_shading(input), Ok((expected_remainder, Some(true))));
+        assert_eq!(smoothing_group(input), Ok((expected_remainder, 0)));
+        assert_eq!(smoothing_group(CompleteStr("s on\r\n")), Ok((expected_remainder, 1)));
+        assert_eq!(smoothing_group(CompleteStr("s 0\n")), Ok((expected_remainder, 0)));
+        assert_eq!(smoothing_group(CompleteStr("s 12\n")), Ok((expected_remainder, 12)));
+        assert!(smoothing_group(CompleteStr("s maybe\n")).is_err());
     }
 
     #[test]
     fn test_parse_polygon_group() {
         let input = CompleteStr("g group1\n");
         let expected_remainder = CompleteStr("");
-        let expected_output = Some(CompleteStr("group1"));
+        let expected_output = vec![CompleteStr("group1")];
+        assert_eq!(polygon_group(input), Ok((expected_remainder, expected_output)));
+
+        let input = CompleteStr("g left-wheel Räder # wheels\n");
+        let expected_output = vec![CompleteStr("left-wheel"), CompleteStr("Räder")];
         assert_eq!(polygon_group(input), Ok((expected_remainder, expected_output)));
     }
 
+    #[test]
+    fn test_parse_names_with_spaces_and_symbols() {
+        assert_eq!(object_name(CompleteStr("o Body/Left\n")), Ok((CompleteStr(""), Cow::Borrowed("Body/Left"))));
+        assert_eq!(object_name(CompleteStr("o My Model # exported\n")), Ok((CompleteStr(""), Cow::Borrowed("My Model"))));
+        assert_eq!(usemtl(CompleteStr("usemtl Material-1\n")), Ok((CompleteStr(""), Cow::Borrowed("Material-1"))));
+        assert_eq!(usemtl(CompleteStr("usemtl Matériau rouge\r\n")), Ok((CompleteStr(""), Cow::Borrowed("Matériau rouge"))));
+    }
+
+    #[test]
+    fn test_parse_obj_file_names_continued() {
+        let s = "mtllib a.mtl \\\n  b.mtl\n\
+                 o My \\\n Model\n\
+                 v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                 usemtl Red \\\n Paint\n\
+                 f 1 2 3\n";
+        let options = ObjOptions {
+            strict: true,
+            ..ObjOptions::default()
+        };
+
+        let model = parse_obj_file_with_options(s, &options).unwrap();
+
+        assert_eq!(model.name, "My Model");
+        assert_eq!(model.material_libraries, vec!["a.mtl b.mtl".to_string()]);
+        assert_eq!(model.submeshes[0].material, Some("Red Paint".to_string()));
+    }
+
     #[test]
     fn test_parse_obj_file() {
         let s = include_str!("../assets/cube_uv.obj");
 
-        let model = parse_obj_file(s);
+        let model = parse_obj_file(s).unwrap();
 
         assert_eq!(model.name, "Cube");
 
@@ -824,13 +1714,20 @@ mod tests {
         assert_eq!(model.triangles[1], 1);
         assert_eq!(model.triangles[2], 2);
         assert_eq!(model.triangles[35], 35);
+
+        assert_eq!(model.submeshes.len(), 1);
+        assert_eq!(model.submeshes[0].material, Some("Material".to_string()));
+        assert_eq!(model.submeshes[0].range, 0..36);
+
+        assert_eq!(model.material_libraries, vec!["cube_uv.mtl".to_string()]);
+        assert!(model.materials.is_empty());
     }
 
     #[test]
     fn test_parse_obj_file_stripped() {
         let s = include_str!("../assets/cube_stripped.obj");
 
-        let model = parse_obj_file(s);
+        let model = parse_obj_file(s).unwrap();
 
         assert_eq!(model.name, "Object");
 
@@ -856,7 +1753,7 @@ mod tests {
     fn test_parse_obj_file_commented() {
         let s = include_str!("../assets/cube_commented.obj");
 
-        let model = parse_obj_file(s);
+        let model = parse_obj_file(s).unwrap();
 
         assert_eq!(model.name, "Object");
 
@@ -882,7 +1779,7 @@ mod tests {
     fn test_parse_obj_file_polygon_groups() {
         let s = include_str!("../assets/cube_polygon_groups.obj");
 
-        let model = parse_obj_file(s);
+        let model = parse_obj_file(s).unwrap();
 
         assert_eq!(model.name, "Cube");
 
@@ -902,5 +1799,842 @@ mod tests {
         assert_eq!(model.triangles[1], 1);
         assert_eq!(model.triangles[2], 2);
         assert_eq!(model.triangles[35], 35);
+
+        assert_eq!(model.groups.len(), 1);
+        assert_eq!(model.groups[0].name, "group1");
+        assert_eq!(model.groups[0].ranges, vec![0..36]);
+    }
+
+    #[test]
+    fn test_parse_obj_file_unreadable_face() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nf 1//1 2//1 x//1\n";
+        let expected_location = Location {
+            line: 6,
+            column: 13,
+            text: "f 1//1 2//1 x//1".to_string(),
+        };
+
+        assert_eq!(parse_obj_file(s).err(), Some(ObjError::Faces(expected_location)));
+    }
+
+    #[test]
+    fn test_parse_obj_file_trailing_comment() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n# end";
+
+        assert_eq!(parse_obj_file(s).unwrap().triangles.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_obj_file_index_out_of_range() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 4//1\n";
+        let expected_location = Location {
+            line: 5,
+            column: 13,
+            text: "f 1//1 2//1 4//1".to_string(),
+        };
+        let expected_error = ObjError::IndexOutOfRange {
+            location: expected_location,
+            attribute: IndexAttribute::Position,
+            index: 4,
+            count: 3,
+        };
+
+        assert_eq!(parse_obj_file(s).err(), Some(expected_error));
+    }
+
+    #[test]
+    fn test_parse_obj_file_zero_index() {
+        let s = "v 0 0 0\r\nv 1 0 0\r\nv 0 1 0\r\nvn 0 0 1\r\n  f 0//1 2//1 3//1\r\n";
+        let expected_location = Location {
+            line: 5,
+            column: 5,
+            text: "  f 0//1 2//1 3//1".to_string(),
+        };
+        let expected_error = ObjError::IndexOutOfRange {
+            location: expected_location,
+            attribute: IndexAttribute::Position,
+            index: 0,
+            count: 3,
+        };
+
+        assert_eq!(parse_obj_file(s).err(), Some(expected_error));
+    }
+
+    #[test]
+    fn test_obj_error_display() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 x//1\n";
+        let err = parse_obj_file(s).err().unwrap();
+
+        assert_eq!(err.location().line, 5);
+        assert_eq!(err.to_string(), "Unable to parse OBJ file: error reading faces at line 5, column 13: `f 1//1 2//1 x//1`");
+    }
+
+    #[test]
+    fn test_parse_face_quad() {
+        let input = CompleteStr("f 1/1/1 2/2/1 3/3/1 4/4/1\n");
+        let expected_remainder = CompleteStr("");
+
+        match face(input) {
+            Ok((remainder, face)) => {
+                assert_eq!(remainder, expected_remainder);
+                assert_eq!(face.vertexes, [1, 2, 3, 4]);
+                assert_eq!(face.texture_coordinates, [Some(1), Some(2), Some(3), Some(4)]);
+                assert_eq!(face.vertex_normals, [Some(1), Some(1), Some(1), Some(1)]);
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_face_two_vertices() {
+        let input = CompleteStr("f 1//1 2//1\n");
+
+        assert!(face(input).is_err());
+    }
+
+    #[test]
+    fn test_parse_obj_file_quad() {
+        let s = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 4//1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.vertices.len(), 4);
+        assert_eq!(model.triangles, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_obj_file_concave_polygon() {
+        let s = "v 0 0 0\nv 2 0 0\nv 1 1 0\nv 1 2 0\nvn 0 0 1\nf 4//1 1//1 3//1 2//1\n";
+
+        let model = parse_obj_file(s).unwrap();
+        assert_eq!(model.triangles.len(), 6);
+        for t in model.triangles.chunks(3) {
+            // Every triangle must use the diagonal between the tip and the notch.
+            assert!(t.contains(&0) && t.contains(&2));
+        }
+
+        let options = ObjOptions {
+            triangulation: Triangulation::Fan,
+            ..ObjOptions::default()
+        };
+        let model = parse_obj_file_with_options(s, &options).unwrap();
+        assert_eq!(model.triangles, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_face_index_forms() {
+        let expected_remainder = CompleteStr(" ");
+
+        assert_eq!(face_index(CompleteStr("3 ")), Ok((expected_remainder, (3, None, None))));
+        assert_eq!(face_index(CompleteStr("3/4 ")), Ok((expected_remainder, (3, Some(4), None))));
+        assert_eq!(face_index(CompleteStr("3//5 ")), Ok((expected_remainder, (3, None, Some(5)))));
+        assert_eq!(face_index(CompleteStr("3/4/5 ")), Ok((expected_remainder, (3, Some(4), Some(5)))));
+    }
+
+    #[test]
+    fn test_parse_face_positions_only() {
+        let input = CompleteStr("f 5 3 1\n");
+        let expected_remainder = CompleteStr("");
+
+        match face(input) {
+            Ok((remainder, face)) => {
+                assert_eq!(remainder, expected_remainder);
+                assert_eq!(face.vertexes, [5, 3, 1]);
+                assert_eq!(face.texture_coordinates, [None, None, None]);
+                assert_eq!(face.vertex_normals, [None, None, None]);
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_face_missing_normals() {
+        let input = CompleteStr("f 5/1 3/2 1/3\n");
+        let expected_remainder = CompleteStr("");
+
+        match face(input) {
+            Ok((remainder, face)) => {
+                assert_eq!(remainder, expected_remainder);
+                assert_eq!(face.vertexes, [5, 3, 1]);
+                assert_eq!(face.texture_coordinates, [Some(1), Some(2), Some(3)]);
+                assert_eq!(face.vertex_normals, [None, None, None]);
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_obj_file_generated_normals() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 0 -1\nvt 0 0\nf 1/1 2/1 3/1\n";
+
+        let model = parse_obj_file(s).unwrap();
+        for v in &model.vertices {
+            assert_eq!((v.normal.x, v.normal.y, v.normal.z), (0.0, 1.0, 0.0));
+        }
+
+        let options = ObjOptions {
+            generate_normals: false,
+            ..ObjOptions::default()
+        };
+        let model = parse_obj_file_with_options(s, &options).unwrap();
+        for v in &model.vertices {
+            assert_eq!((v.normal.x, v.normal.y, v.normal.z), (0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn test_parse_face_index_negative() {
+        let input = CompleteStr("-1/-2/-3 ");
+        let expected_remainder = CompleteStr(" ");
+
+        assert_eq!(face_index(input), Ok((expected_remainder, (-1, Some(-2), Some(-3)))));
+    }
+
+    #[test]
+    fn test_resolve_index() {
+        assert_eq!(resolve_index(1, 3), Some(0));
+        assert_eq!(resolve_index(3, 3), Some(2));
+        assert_eq!(resolve_index(4, 3), None);
+        assert_eq!(resolve_index(0, 3), None);
+        assert_eq!(resolve_index(-1, 3), Some(2));
+        assert_eq!(resolve_index(-3, 3), Some(0));
+        assert_eq!(resolve_index(-4, 3), None);
+    }
+
+    #[test]
+    fn test_parse_obj_file_relative_indices() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\nf -3/-2/-1 -2/-1/-1 -1/-1/-1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.vertices.len(), 3);
+        assert_eq!(model.vertices[0].p.x, 0.0);
+        assert_eq!(model.vertices[1].p.x, 1.0);
+        assert_eq!(model.vertices[2].p.y, 1.0);
+        assert_eq!(model.vertices[0].uv.x, 0.0);
+        assert_eq!(model.vertices[1].uv.x, 1.0);
+        assert_eq!(model.vertices[2].normal.z, 1.0);
+    }
+
+    #[test]
+    fn test_parse_obj_file_normal_index_out_of_range() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//-2 3//1\n";
+        let err = parse_obj_file(s).err().unwrap();
+
+        match err {
+            ObjError::IndexOutOfRange { attribute, index, count, .. } => {
+                assert_eq!(attribute, IndexAttribute::Normal);
+                assert_eq!(index, -2);
+                assert_eq!(count, 1);
+            },
+            _ => panic!("unexpected error {:?}", err)
+        }
+        assert_eq!(err.to_string(), "Unable to parse OBJ file: normal index -2 out of range (valid range 1 to 1 or -1 to -1) at line 5, column 11: `f 1//1 2//-2 3//1`");
+    }
+
+    #[test]
+    fn test_parse_obj_file_missing_texture_coordinates() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n";
+        let err = parse_obj_file(s).err().unwrap();
+
+        assert_eq!(err.to_string(), "Unable to parse OBJ file: texture coordinate index 1 out of range, no texture coordinates defined at line 4, column 5: `f 1/1 2/1 3/1`");
+    }
+
+    #[test]
+    fn test_parse_obj_file_interleaved_statements() {
+        let s = "o Interleaved\n\
+                 v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\n\
+                 g first\ns off\nf 1//1 2//1 3//1\n\
+                 v 1 1 0\nvt 0.5 0.5\n\
+                 usemtl Material\ng second\nf 2/1/1 4/1/1 3/1/1\n\
+                 o Second\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.name, "Interleaved");
+        assert_eq!(model.vertices.len(), 6);
+        assert_eq!(model.triangles.len(), 6);
+        assert_eq!(model.vertices[4].p.x, 1.0);
+        assert_eq!(model.vertices[4].p.y, 1.0);
+        assert_eq!(model.vertices[4].uv.x, 0.5);
+    }
+
+    #[test]
+    fn test_parse_obj_file_relative_indices_interleaved() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\nf -4 -3 -1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.vertices[2].p.y, 1.0);
+        assert_eq!(model.vertices[5].p.x, 5.0);
+    }
+
+    #[test]
+    fn test_parse_obj_file_unknown_statement() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n  bogus 1 2\nf 1 2 3\n";
+        let expected_location = Location {
+            line: 5,
+            column: 3,
+            text: "  bogus 1 2".to_string(),
+        };
+
+        let options = ObjOptions {
+            strict: true,
+            ..ObjOptions::default()
+        };
+        assert_eq!(parse_obj_file_with_options(s, &options).err(), Some(ObjError::UnknownStatement(expected_location)));
+    }
+
+    #[test]
+    fn test_parse_obj_file_bad_vertex_after_faces() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nv 1 x 0\n";
+        let expected_location = Location {
+            line: 5,
+            column: 5,
+            text: "v 1 x 0".to_string(),
+        };
+
+        assert_eq!(parse_obj_file(s).err(), Some(ObjError::VertexPositions(expected_location)));
+    }
+
+    #[test]
+    fn test_parse_obj_scene() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+                 o First\nf 1 2 3\n\
+                 o Second\nf 2 4 3\nf -4 -3 -2\n";
+
+        let scene = parse_obj_scene(s).unwrap();
+
+        assert_eq!(scene.models.len(), 2);
+        assert_eq!(scene.models[0].name, "First");
+        assert_eq!(scene.models[0].triangles.len(), 3);
+        assert_eq!(scene.models[1].name, "Second");
+        assert_eq!(scene.models[1].triangles.len(), 6);
+        assert_eq!(scene.models[1].vertices[1].p.x, 1.0);
+        assert_eq!(scene.models[1].vertices[1].p.y, 1.0);
+    }
+
+    #[test]
+    fn test_parse_obj_scene_faces_before_first_object() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\no Named\nf 3 2 1\n";
+
+        let scene = parse_obj_scene(s).unwrap();
+
+        assert_eq!(scene.models.len(), 2);
+        assert_eq!(scene.models[0].name, "Object");
+        assert_eq!(scene.models[1].name, "Named");
+    }
+
+    #[test]
+    fn test_parse_obj_scene_single_object() {
+        let s = include_str!("../assets/cube_uv.obj");
+
+        let scene = parse_obj_scene(s).unwrap();
+
+        assert_eq!(scene.models.len(), 1);
+        assert_eq!(scene.models[0].name, "Cube");
+        assert_eq!(scene.models[0].triangles.len(), 12 * 3);
+    }
+
+    #[test]
+    fn test_parse_obj_file_multiple_objects() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\no First\nf 1 2 3\no Second\nf 3 2 1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.name, "First");
+        assert_eq!(model.triangles.len(), 6);
+    }
+
+    #[test]
+    fn test_parse_polygon_group_multiple_names() {
+        let input = CompleteStr("g wheel  front left\n");
+        let expected_remainder = CompleteStr("");
+        let expected_output = vec![CompleteStr("wheel"), CompleteStr("front"), CompleteStr("left")];
+
+        assert_eq!(polygon_group(input), Ok((expected_remainder, expected_output)));
+    }
+
+    #[test]
+    fn test_parse_polygon_group_default() {
+        let input = CompleteStr("g\n");
+        let expected_remainder = CompleteStr("");
+
+        assert_eq!(polygon_group(input), Ok((expected_remainder, vec![CompleteStr("default")])));
+    }
+
+    #[test]
+    fn test_parse_obj_file_groups() {
+        let s = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                 f 1 2 3\n\
+                 g body\nf 1 2 3 4\n\
+                 g wheel left\nf 1 2 3\nf 1 3 4\n\
+                 g body\nf 4 3 2\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.groups.len(), 4);
+        assert_eq!(model.group("default").unwrap().ranges, vec![0..3]);
+        assert_eq!(model.group("body").unwrap().ranges, vec![3..9, 15..18]);
+        assert_eq!(model.group("wheel").unwrap().ranges, vec![9..15]);
+        assert_eq!(model.group("left").unwrap().ranges, vec![9..15]);
+    }
+
+    #[test]
+    fn test_parse_obj_file_material_submeshes() {
+        let s = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                 f 1 2 3\n\
+                 usemtl Red\nf 1 2 3 4\n\
+                 usemtl Blue\nf 1 2 3\ng part\nf 1 3 4\n\
+                 usemtl Blue\nf 4 3 2\n\
+                 usemtl Red\nf 1 2 3\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        let submeshes: Vec<_> = model.submeshes.iter()
+            .map(|s| (s.material.as_deref(), s.range.clone()))
+            .collect();
+        assert_eq!(submeshes, vec![
+            (None, 0..3),
+            (Some("Red"), 3..9),
+            (Some("Blue"), 9..18),
+            (Some("Red"), 18..21),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_obj_scene_material_carries_across_objects() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl Red\no First\nf 1 2 3\no Second\nf 1 2 3\n";
+
+        let scene = parse_obj_scene(s).unwrap();
+
+        assert_eq!(scene.models[1].submeshes.len(), 1);
+        assert_eq!(scene.models[1].submeshes[0].material, Some("Red".to_string()));
+        assert_eq!(scene.models[1].submeshes[0].range, 0..3);
+    }
+
+    #[test]
+    fn test_parse_obj_file_tab_separated() {
+        let s = "o\tTabbed\n\
+                 v\t0.0\t0.0\t0.0\n\
+                 v 1.0\t\t0.0 0.0 \t\n\
+                 v\t0.0 1.0\t0.0\n\
+                 vt\t0.5\t0.5\n\
+                 vn\t0\t0\t1\n\
+                 g\tleft\tright\n\
+                 usemtl\tRed\t\n\
+                 s\toff\n\
+                 f\t1/1/1\t2/1/1 \t3/1/1\t\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.name, "Tabbed");
+        assert_eq!(model.vertices.len(), 3);
+        assert_eq!(model.vertices[1].p.x, 1.0);
+        assert_eq!(model.vertices[2].uv.x, 0.5);
+        assert_eq!(model.groups.len(), 2);
+        assert_eq!(model.submeshes[0].material, Some("Red".to_string()));
+    }
+
+    #[test]
+    fn test_parse_obj_file_without_final_newline() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3";
+
+        let model = parse_obj_file(s).unwrap();
+        assert_eq!(model.triangles.len(), 3);
+
+        let s = "v 0 0 0\r\nv 1 0 0\r\nv 0 1 0\r\nf 1 2 3  # last face";
+
+        let model = parse_obj_file(s).unwrap();
+        assert_eq!(model.triangles.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_obj_file_line_continuation() {
+        let s = "v 0 0 0\nv 1 0 0\nv 1 1 \\\n0\nv 0 1 0\nv -1 1 0\n\
+                 g first \\\n  second\\\n  third\n\
+                 f 1 2 \\\n  3 4 \\\r\n  5\n\
+                 f 1 2 3\\\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.vertices[2].p.x, 1.0);
+        assert_eq!(model.vertices[2].p.z, 0.0);
+        assert_eq!(model.triangles.len(), 3 * 3 + 3);
+        assert_eq!(model.groups.len(), 3);
+        assert_eq!(model.group("third").unwrap().ranges, vec![0..12]);
+    }
+
+    #[test]
+    fn test_parse_obj_file_line_continuation_error_location() {
+        let s = "v 0 0 0\nf 1 \\\n  1 \\\n  x\n";
+
+        let location = parse_obj_file(s).err().unwrap().location().clone();
+
+        assert_eq!(location.line, 4);
+        assert_eq!(location.column, 3);
+        assert_eq!(location.text, "  x");
+    }
+
+    #[test]
+    fn test_parse_vertex_with_color() {
+        let input = CompleteStr("v 1.0 2.0 3.0 0.5 0.25 1\n");
+
+        match vertex(input) {
+            Ok((remainder, (v, w, c))) => {
+                assert_eq!(remainder, CompleteStr(""));
+                assert_eq!((v.x, v.y, v.z), (1.0, 2.0, 3.0));
+                assert_eq!(w, 1.0);
+                let c = c.unwrap();
+                assert_eq!((c.x, c.y, c.z), (0.5, 0.25, 1.0));
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_obj_file_vertex_colors() {
+        let s = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0\nf 1 2 3\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        let red = model.vertices[0].color.unwrap();
+        assert_eq!((red.x, red.y, red.z), (1.0, 0.0, 0.0));
+        let green = model.vertices[1].color.unwrap();
+        assert_eq!((green.x, green.y, green.z), (0.0, 1.0, 0.0));
+        assert!(model.vertices[2].color.is_none());
+    }
+
+    #[test]
+    fn test_parse_vertex_with_weight() {
+        match vertex(CompleteStr("v 1.0 2.0 3.0 0.5\n")) {
+            Ok((_, (_, w, c))) => {
+                assert_eq!(w, 0.5);
+                assert!(c.is_none());
+            },
+            Err(err) => panic!("{:?}", err)
+        }
+
+        assert!(vertex(CompleteStr("v 1.0 2.0 3.0 0.5 1\n")).is_err());
+        assert!(vertex(CompleteStr("v 1.0 2.0 3.0 0.5 1 0 0\n")).is_err());
+    }
+
+    #[test]
+    fn test_parse_obj_file_weights_and_3d_texture_coordinates() {
+        let s = "v 0 0 0 2\nv 1 0 0\nv 0 1 0\nvt 0.5 0.5 0.75\nvt 0.25 0.25\nf 1/1 2/2 3/1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.vertices[0].w, 2.0);
+        assert_eq!(model.vertices[1].w, 1.0);
+        assert_eq!(model.vertices[0].uv.z, 0.75);
+        assert_eq!(model.vertices[1].uv.z, 0.0);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let (remainder, l) = line(CompleteStr("l 1/2 2/3 -1\n")).unwrap();
+
+        assert_eq!(remainder, CompleteStr(""));
+        assert_eq!(l.kind, ElementKind::Line);
+        assert_eq!(l.vertexes, vec![1, 2, -1]);
+        assert_eq!(l.texture_coordinates, vec![Some(2), Some(3), None]);
+        assert_eq!(l.vertex_normals, vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_parse_point() {
+        let (remainder, p) = point(CompleteStr("p 1 2 3\n")).unwrap();
+
+        assert_eq!(remainder, CompleteStr(""));
+        assert_eq!(p.kind, ElementKind::Point);
+        assert_eq!(p.vertexes, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_obj_file_lines_and_points() {
+        let s = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0.5 0.5\n\
+                 f 1 2 3\n\
+                 l 1/1 2/1 3/1 4/1\n\
+                 p 1 4\n\
+                 l 4 1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.triangles, vec![0, 1, 2]);
+        assert_eq!(model.lines, vec![[3, 4], [4, 5], [5, 6], [9, 10]]);
+        assert_eq!(model.points, vec![7, 8]);
+        assert_eq!(model.vertices[5].p.x, 1.0);
+        assert_eq!(model.vertices[5].uv.x, 0.5);
+        assert_eq!(model.vertices[8].p.y, 1.0);
+        assert_eq!(model.submeshes.len(), 1);
+        assert_eq!(model.submeshes[0].range, 0..3);
+    }
+
+    #[test]
+    fn test_parse_obj_file_line_with_normal_index() {
+        let s = "v 0 0 0\nv 1 0 0\nvn 0 0 1\nl 1//1 2//1\n";
+
+        let expected_location = Location {
+            line: 4,
+            column: 4,
+            text: "l 1//1 2//1".to_string(),
+        };
+        assert_eq!(parse_obj_file(s).err(), Some(ObjError::Lines(expected_location)));
+    }
+
+    // Two faces meeting at a right angle along the edge from vertex 1 to vertex 2.
+    fn folded_quads(smoothing: &str) -> String {
+        format!("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 1 0 1\nv 0 0 1\n\
+                 {}\nf 1 2 3 4\n{}\nf 6 5 2 1\n", smoothing, smoothing)
+    }
+
+    #[test]
+    fn test_parse_obj_file_smoothing_group_normals() {
+        let model = parse_obj_file(&folded_quads("s 1")).unwrap();
+
+        assert_eq!(model.smoothing_groups, vec![1, 1, 1, 1]);
+
+        // Vertex 1 of the first face is on the shared edge, so it is smoothed.
+        let n = &model.vertices[0].normal;
+        let d = 0.5f32.sqrt();
+        assert!((n.x - 0.0).abs() < 1e-6 && (n.y - d).abs() < 1e-6 && (n.z - d).abs() < 1e-6);
+        // Vertex 3 is only on the first face.
+        let n = &model.vertices[2].normal;
+        assert_eq!((n.x, n.y, n.z), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_parse_obj_file_smoothing_off_normals() {
+        let model = parse_obj_file(&folded_quads("s off")).unwrap();
+
+        assert_eq!(model.smoothing_groups, vec![0, 0, 0, 0]);
+        let n = &model.vertices[0].normal;
+        assert_eq!((n.x, n.y, n.z), (0.0, 0.0, 1.0));
+        let n = &model.vertices[7].normal;
+        assert_eq!((n.x, n.y, n.z), (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_obj_file_different_smoothing_groups_stay_hard() {
+        let s = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 1 0 1\nv 0 0 1\n\
+                 s 1\nf 1 2 3 4\ns 2\nf 6 5 2 1\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.smoothing_groups, vec![1, 1, 2, 2]);
+        let n = &model.vertices[0].normal;
+        assert_eq!((n.x, n.y, n.z), (0.0, 0.0, 1.0));
+        let n = &model.vertices[7].normal;
+        assert_eq!((n.x, n.y, n.z), (0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_curve_type() {
+        assert_eq!(curve_type(CompleteStr("cstype rat bspline\n")), Ok((CompleteStr(""), (Basis::BSpline, true))));
+        assert_eq!(curve_type(CompleteStr("cstype bezier\n")), Ok((CompleteStr(""), (Basis::Bezier, false))));
+        assert!(curve_type(CompleteStr("cstype nurbs\n")).is_err());
+        // Basis matrices and step sizes from `bmat` and `step` are not read.
+        assert!(curve_type(CompleteStr("cstype bmatrix\n")).is_err());
+        assert!(curve_type(CompleteStr("cstype rat taylor\n")).is_err());
+    }
+
+    #[test]
+    fn test_parse_trim() {
+        let (remainder, curves) = trim(CompleteStr("trim 0 1 1 0.5 1 -1\n")).unwrap();
+
+        assert_eq!(remainder, CompleteStr(""));
+        assert_eq!(curves, vec![(0.0, 1.0, 1), (0.5, 1.0, -1)]);
+    }
+
+    // A bilinear B-spline patch over the unit square, trimmed by a parameter space curve.
+    const BSPLINE_SURFACE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 1\n\
+                                   vp 0 0\nvp 1 0\nvp 1 1\n\
+                                   cstype bspline\ndeg 1\n\
+                                   curv2 1 2 3\nparm u 0 0 1 2 2\nend\n\
+                                   deg 1 1\n\
+                                   usemtl Patch\n\
+                                   surf 0 1 0 1 1 2 3 4\n\
+                                   parm u 0 0 1 1\nparm v 0 0 1 1\n\
+                                   trim 0 2 1\n\
+                                   hole 0 1 -1\n\
+                                   end\n";
+
+    #[test]
+    fn test_parse_obj_file_surface() {
+        let model = parse_obj_file(BSPLINE_SURFACE).unwrap();
+
+        assert!(model.triangles.is_empty());
+        assert_eq!(model.surfaces.len(), 1);
+        let surface = &model.surfaces[0];
+        assert_eq!(surface.basis, Basis::BSpline);
+        assert_eq!((surface.degree_u, surface.degree_v), (1, 1));
+        assert_eq!(surface.control_points.len(), 4);
+        assert_eq!(surface.control_points[3].p.z, 1.0);
+        assert_eq!(surface.knots_u, vec![0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(surface.material, Some("Patch".to_string()));
+        assert_eq!(surface.trims.len(), 1);
+        assert_eq!(surface.trims[0].len(), 1);
+        assert_eq!(surface.trims[0][0].end, 2.0);
+        assert_eq!(surface.trims[0][0].curve.control_points.len(), 3);
+        assert_eq!(surface.trims[0][0].curve.control_points[2].y, 1.0);
+        assert_eq!(surface.trims[0][0].curve.knots, vec![0.0, 0.0, 1.0, 2.0, 2.0]);
+        assert_eq!(surface.holes.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_obj_file_tessellated_surface() {
+        let options = ObjOptions {
+            tessellation: Some(2),
+            ..ObjOptions::default()
+        };
+
+        let model = parse_obj_file_with_options(BSPLINE_SURFACE, &options).unwrap();
+
+        // The trim keeps the half of the patch below the diagonal.
+        assert_eq!(model.vertices.len(), 6);
+        assert_eq!(model.triangles.len(), 12);
+        assert_eq!(model.smoothing_groups.len(), 4);
+        let p = &model.vertices[3].p;
+        assert_eq!((p.x, p.y, p.z), (0.5, 0.5, 0.25));
+        assert_eq!(model.submeshes.len(), 1);
+        assert_eq!(model.submeshes[0].material, Some("Patch".to_string()));
+        assert_eq!(model.submeshes[0].range, 0..12);
+    }
+
+    #[test]
+    fn test_parse_obj_file_curve() {
+        let s = "v 0 0 0\nv 1 0 0 2\nv 2 1 0\n\
+                 cstype rat bezier\ndeg 2\n\
+                 curv 0 1 1 2 3\nparm u 0 1\nend\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.curves.len(), 1);
+        let curve = &model.curves[0];
+        assert_eq!(curve.basis, Basis::Bezier);
+        assert!(curve.rational);
+        assert_eq!(curve.degree, 2);
+        assert_eq!((curve.start, curve.end), (0.0, 1.0));
+        assert_eq!(curve.control_points[1].w, 2.0);
+        assert_eq!(curve.knots, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_parse_obj_scene_free_form_objects() {
+        let s = "v 0 0 0\nv 1 0 0\nv 2 1 0\ncstype bezier\ndeg 2\n\
+                 o first\ncurv 0 1 1 2 3\nend\n\
+                 o second\ncurv 0 1 3 2 1\nend\n";
+
+        let scene = parse_obj_scene(s).unwrap();
+
+        assert_eq!(scene.models.len(), 2);
+        assert_eq!(scene.models[0].curves.len(), 1);
+        assert_eq!(scene.models[1].curves[0].control_points[0].p.x, 2.0);
+    }
+
+    #[test]
+    fn test_parse_obj_file_surface_with_huge_degree() {
+        let s = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 1\n\
+                         cstype bspline\ndeg {} 1\n\
+                         surf 0 1 0 1 1 2 3 4\nparm u 0 0 1 1\nparm v 0 0 1 1\nend\n", usize::MAX);
+        let options = ObjOptions {
+            tessellation: Some(2),
+            ..ObjOptions::default()
+        };
+
+        let model = parse_obj_file_with_options(&s, &options).unwrap();
+
+        assert_eq!(model.surfaces.len(), 1);
+        assert!(model.triangles.is_empty());
+    }
+
+    #[test]
+    fn test_parse_obj_file_free_form_errors() {
+        let s = "v 0 0 0\nv 1 0 0\ncurv 0 1 1 2\nend\n";
+
+        let expected_location = Location {
+            line: 3,
+            column: 1,
+            text: "curv 0 1 1 2".to_string(),
+        };
+        assert_eq!(parse_obj_file(s).err(), Some(ObjError::FreeForm(expected_location)));
+
+        let s = "cstype bezier\ndeg 1\nparm u 0 1\n";
+        match parse_obj_file(s).err().unwrap() {
+            ObjError::FreeForm(location) => assert_eq!(location.line, 3),
+            err => panic!("unexpected error {:?}", err)
+        }
+
+        let s = "v 0 0 0\nv 1 0 0\ncstype bezier\ndeg 1\ncurv 0 1 1 2\n";
+        match parse_obj_file(s).err().unwrap() {
+            ObjError::FreeForm(location) => assert_eq!(location.line, 5),
+            err => panic!("unexpected error {:?}", err)
+        }
+
+        let s = "v 0 0 0\nv 1 0 0\ncstype bmatrix\ndeg 1\nstep 1\nbmat u 1 -1 0 1\ncurv 0 1 1 2\nend\n";
+        match parse_obj_file(s).err().unwrap() {
+            ObjError::FreeForm(location) => assert_eq!(location.line, 3),
+            err => panic!("unexpected error {:?}", err)
+        }
+
+        let s = "v 0 0 0\nv 1 0 0\ncstype bspline\ndeg 1 1\nsurf 0 1 0 1 1 2\ntrim 0 1 1\nend\n";
+        match parse_obj_file(s).err().unwrap() {
+            ObjError::IndexOutOfRange { attribute, index, count, .. } => {
+                assert_eq!(attribute, IndexAttribute::ParameterCurve);
+                assert_eq!(index, 1);
+                assert_eq!(count, 0);
+            },
+            err => panic!("unexpected error {:?}", err)
+        }
+    }
+
+    #[test]
+    fn test_parse_obj_file_keeps_unknown_statements() {
+        let s = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                 mg 1 0.5\n\
+                 lod 50\n\
+                 f 1 2 3\n\
+                 shadow_obj shadow file.obj\n\
+                 \n\
+                 c_interp on \\\n  # interpolate\n\
+                 x-custom-tag 1 \\\n 2\n";
+
+        let model = parse_obj_file(s).unwrap();
+
+        assert_eq!(model.triangles, vec![0, 1, 2]);
+        let unknown: Vec<_> = model.unknown_statements.iter()
+            .map(|u| (u.line, u.keyword.as_str(), u.arguments.as_str()))
+            .collect();
+        assert_eq!(unknown, vec![
+            (4, "mg", "1 0.5"),
+            (5, "lod", "50"),
+            (7, "shadow_obj", "shadow file.obj"),
+            (9, "c_interp", "on"),
+            (11, "x-custom-tag", "1 2"),
+        ]);
+        assert_eq!(model.unknown_statements[1].to_string(), "line 5: unrecognized statement `lod`");
+    }
+
+    #[test]
+    fn test_parse_obj_scene_keeps_unknown_statements() {
+        let s = "o first\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nlod 10\no second\nbevel off\nf 1 2 3\n";
+
+        let scene = parse_obj_scene(s).unwrap();
+
+        assert_eq!(scene.models.len(), 2);
+        assert!(scene.models[0].unknown_statements.is_empty());
+        assert_eq!(scene.unknown_statements.len(), 2);
+        assert_eq!(scene.unknown_statements[1].line, 8);
+        assert_eq!(scene.unknown_statements[1].keyword, "bevel");
+
+        let options = ObjOptions {
+            strict: true,
+            ..ObjOptions::default()
+        };
+        match parse_obj_scene_with_options(s, &options).err().unwrap() {
+            ObjError::UnknownStatement(location) => assert_eq!(location.line, 6),
+            err => panic!("unexpected error {:?}", err)
+        }
     }
 }
@@ -0,0 +1,349 @@
+use std::borrow::Cow;
+use std::fmt;
+use nom::*;
+use nom::types::CompleteStr;
+
+/*
+    Basic Parsers
+*/
+
+// Tokens are separated by any mix of spaces and tabs.
+pub(crate) fn is_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+// A `\` at the end of a line continues the statement on the next line.
+named!(continuation<CompleteStr, CompleteStr>,
+    recognize!(
+        preceded!(
+            tag!("\\"),
+            alt!(line_ending | eof!())
+        )
+    )
+);
+
+// Line continuations count as whitespace, so long statements can be split over
+// several lines while locations still refer to the original line numbers.
+named!(pub(crate) spaces<CompleteStr, CompleteStr>,
+    recognize!(
+        many1!(
+            alt!(take_while1!(is_space) | continuation)
+        )
+    )
+);
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && c != '#'
+}
+
+// A single whitespace separated token, such as one of several group names.
+pub(crate) fn word(input: CompleteStr) -> IResult<CompleteStr, CompleteStr> {
+    let (remainder, w) = take_while1!(input, is_word_char)?;
+
+    // A `\` directly before the line ending is a continuation, not part of the word.
+    if w.ends_with('\\') && continuation(CompleteStr(&input[w.len() - 1..])).is_ok() {
+        if w.len() == 1 {
+            return Err(Err::Error(Context::Code(input, ErrorKind::TakeWhile1)));
+        }
+        return Ok((CompleteStr(&input[w.len() - 1..]), CompleteStr(&w[..w.len() - 1])));
+    }
+
+    Ok((remainder, w))
+}
+
+// The rest of the line up to any comment, without trailing whitespace. Object, material
+// and file names may contain spaces, hyphens, path separators and non-ASCII characters.
+// A name continued over several lines is joined with single spaces.
+pub(crate) fn name(input: CompleteStr) -> IResult<CompleteStr, Cow<str>> {
+    let (remainder, text) = recognize!(input,
+        many1!(
+            alt!(
+                take_till1!(|c: char| c == '#' || c == '\\' || c == '\r' || c == '\n') |
+                continuation |
+                tag!("\\")
+            )
+        )
+    )?;
+
+    let name = join_continuations(text);
+    if name.is_empty() {
+        return Err(Err::Error(Context::Code(input, ErrorKind::Verify)));
+    }
+    Ok((remainder, name))
+}
+
+// Joins the lines of text continued with `\` with single spaces.
+fn join_continuations(text: CompleteStr) -> Cow<str> {
+    let text = text.0.trim_end();
+    // A continuation at the end of the text, before a comment or the end of the file.
+    let text = text.strip_suffix('\\').unwrap_or(text).trim_end();
+    if text.contains('\n') {
+        let lines = text.split('\n')
+            .map(|line| line.trim_end_matches('\r'))
+            .map(|line| line.strip_suffix('\\').unwrap_or(line).trim())
+            .filter(|line| !line.is_empty());
+        Cow::Owned(lines.collect::<Vec<_>>().join(" "))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+// Consumes nothing, returning the input at the current position.
+pub(crate) fn current_position(input: CompleteStr) -> IResult<CompleteStr, CompleteStr> {
+    Ok((input, input))
+}
+
+// The end of a statement. The last line of a file may have no line ending.
+named!(pub(crate) line_end<CompleteStr, CompleteStr>,
+    preceded!(
+        opt!(spaces),
+        alt!(line_ending | comment | eof!())
+    )
+);
+
+named!(pub(crate) empty_line<CompleteStr, CompleteStr>,
+    preceded!(
+        opt!(spaces),
+        line_ending
+    )
+);
+
+/*
+    Comments
+*/
+
+named!(pub(crate) comment<CompleteStr, CompleteStr>,
+    do_parse!(
+        tag!("#") >>
+        comment: take_till!(|c: char| c == '\r' || c == '\n') >>
+        alt!(line_ending | eof!()) >>
+
+        (comment)
+    )
+);
+
+named!(pub(crate) ignore_line<CompleteStr, CompleteStr>,
+    alt!(empty_line | comment)
+);
+
+named!(pub(crate) ignore_lines<CompleteStr, Vec<CompleteStr>>,
+    many0!(ignore_line)
+);
+
+pub(crate) fn only_ignored_lines(data: CompleteStr) -> bool {
+    data.lines().all(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with('#')
+    })
+}
+
+// The statement at the start of `input`, without its indentation.
+pub(crate) fn statement_start(input: CompleteStr) -> CompleteStr {
+    match spaces(input) {
+        Ok((rest, _)) => rest,
+        Err(_) => input
+    }
+}
+
+// The keyword that starts a statement.
+named!(pub(crate) keyword<CompleteStr, CompleteStr>,
+    preceded!(
+        opt!(spaces),
+        take_till!(|c: char| c.is_whitespace() || c == '#')
+    )
+);
+
+/*
+    Unknown Statements
+*/
+
+// Everything after the keyword up to any comment, including line continuations.
+named!(arguments<CompleteStr, Cow<str>>,
+    map!(
+        recognize!(
+            many1!(
+                alt!(
+                    take_while1!(|c: char| c != '#' && c != '\\' && c != '\r' && c != '\n') |
+                    continuation |
+                    tag!("\\")
+                )
+            )
+        ),
+        join_continuations
+    )
+);
+
+// A statement with a keyword this parser does not know, such as a vendor extension. Returns the keyword and its arguments.
+named!(pub(crate) unknown_statement<CompleteStr, (CompleteStr, Cow<str>)>,
+    do_parse!(
+        opt!(spaces) >>
+        keyword: take_till1!(|c: char| c.is_whitespace() || c == '#') >>
+        arguments: opt!(preceded!(spaces, arguments)) >>
+        line_end >>
+
+        (keyword, arguments.unwrap_or(Cow::Borrowed("")))
+    )
+);
+
+/*
+    Errors
+*/
+
+/// Position of a parse failure within the source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in characters.
+    pub column: usize,
+    /// The full text of the offending line.
+    pub text: String,
+}
+
+impl Location {
+    pub(crate) fn new(data: &str, remainder: CompleteStr) -> Location {
+        let offset = data.len() - remainder.len();
+        let consumed = &data[..offset];
+        let line_start = match consumed.rfind('\n') {
+            Some(i) => i + 1,
+            None => 0
+        };
+
+        Location {
+            line: consumed.matches('\n').count() + 1,
+            column: data[line_start..offset].chars().count() + 1,
+            text: data[line_start..].lines().next().unwrap_or("").to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: `{}`", self.line, self.column, self.text)
+    }
+}
+
+// The input at which a parser gave up, falling back to where it started.
+fn failure_point<'a>(err: &Err<CompleteStr<'a>>, input: CompleteStr<'a>) -> CompleteStr<'a> {
+    match *err {
+        Err::Error(Context::Code(i, _)) | Err::Failure(Context::Code(i, _)) => i,
+        _ => input
+    }
+}
+
+pub(crate) fn error_location(data: &str, err: &Err<CompleteStr>, input: CompleteStr) -> Location {
+    Location::new(data, failure_point(err, input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name() {
+        let input = CompleteStr("cube\n");
+        let expected_remainder = CompleteStr("\n");
+        let expected_output = Cow::Borrowed("cube");
+        assert_eq!(name(input), Ok((expected_remainder, expected_output)));
+    }
+
+    #[test]
+    fn test_parse_name_rest_of_line() {
+        assert_eq!(name(CompleteStr("my model.mtl  \n")), Ok((CompleteStr("\n"), Cow::Borrowed("my model.mtl"))));
+        assert_eq!(name(CompleteStr("Material-1 # red\n")), Ok((CompleteStr("# red\n"), Cow::Borrowed("Material-1"))));
+        assert_eq!(name(CompleteStr("Body/Left\r\n")), Ok((CompleteStr("\r\n"), Cow::Borrowed("Body/Left"))));
+        assert_eq!(name(CompleteStr("Würfel")), Ok((CompleteStr(""), Cow::Borrowed("Würfel"))));
+        assert!(name(CompleteStr("  # comment\n")).is_err());
+    }
+
+    #[test]
+    fn test_parse_name_continued() {
+        assert_eq!(name(CompleteStr("My \\\n  Model\n")), Ok((CompleteStr("\n"), Cow::Owned("My Model".to_string()))));
+        assert_eq!(name(CompleteStr("a.mtl\\\r\nb.mtl\r\n")), Ok((CompleteStr("\r\n"), Cow::Owned("a.mtl b.mtl".to_string()))));
+        assert_eq!(name(CompleteStr("cube \\\n# comment\n")), Ok((CompleteStr("# comment\n"), Cow::Borrowed("cube"))));
+        assert_eq!(name(CompleteStr("textures\\wood.mtl\n")), Ok((CompleteStr("\n"), Cow::Borrowed("textures\\wood.mtl"))));
+        assert!(name(CompleteStr("\\\n\n")).is_err());
+    }
+
+    #[test]
+    fn test_parse_word() {
+        assert_eq!(word(CompleteStr("left-wheel body\n")), Ok((CompleteStr(" body\n"), CompleteStr("left-wheel"))));
+        assert_eq!(word(CompleteStr("body#comment\n")), Ok((CompleteStr("#comment\n"), CompleteStr("body"))));
+    }
+
+    #[test]
+    fn test_parse_comment() {
+        let input = CompleteStr("#this is a comment\n");
+        let expected_remainder = CompleteStr("");
+        let expected_output = CompleteStr("this is a comment");
+        
+        assert_eq!(comment(input), Ok((expected_remainder, expected_output)));
+    }
+
+    #[test]
+    fn test_parse_comment_crlf() {
+        let input = CompleteStr("#this is a comment\r\nNext Line");
+        let expected_remainder = CompleteStr("Next Line");
+        let expected_output = CompleteStr("this is a comment");
+        
+        assert_eq!(comment(input), Ok((expected_remainder, expected_output)));
+    }
+
+    #[test]
+    fn test_parse_comment_at_end_of_file() {
+        assert_eq!(comment(CompleteStr("#last line")), Ok((CompleteStr(""), CompleteStr("last line"))));
+    }
+
+    #[test]
+    fn test_parse_line_end() {
+        assert_eq!(line_end(CompleteStr(" \t\r\nv")), Ok((CompleteStr("v"), CompleteStr("\r\n"))));
+        assert_eq!(line_end(CompleteStr("\t# comment\nv")), Ok((CompleteStr("v"), CompleteStr(" comment"))));
+        assert_eq!(line_end(CompleteStr("  ")), Ok((CompleteStr(""), CompleteStr(""))));
+        assert!(line_end(CompleteStr(" 1\n")).is_err());
+    }
+
+    #[test]
+    fn test_parse_spaces_and_tabs() {
+        assert_eq!(spaces(CompleteStr(" \t \tx")), Ok((CompleteStr("x"), CompleteStr(" \t \t"))));
+    }
+
+    #[test]
+    fn test_parse_spaces_with_continuation() {
+        assert_eq!(spaces(CompleteStr(" \\\n  2")), Ok((CompleteStr("2"), CompleteStr(" \\\n  "))));
+        assert_eq!(spaces(CompleteStr("\\\r\n2")), Ok((CompleteStr("2"), CompleteStr("\\\r\n"))));
+        assert!(spaces(CompleteStr("\\2")).is_err());
+    }
+
+    #[test]
+    fn test_parse_word_before_continuation() {
+        assert_eq!(word(CompleteStr("left\\\nright")), Ok((CompleteStr("\\\nright"), CompleteStr("left"))));
+        assert_eq!(word(CompleteStr("a\\b ")), Ok((CompleteStr(" "), CompleteStr("a\\b"))));
+    }
+
+    #[test]
+    fn test_parse_spaces() {
+        let input = CompleteStr("   spaces  ");
+        let expected_remainder = CompleteStr("spaces  ");
+        let expected_output = CompleteStr("   ");
+
+        assert_eq!(spaces(input), Ok((expected_remainder, expected_output)));
+    }
+
+    #[test]
+    fn test_parse_keyword() {
+        assert_eq!(keyword(CompleteStr("  usemtl Material\n")), Ok((CompleteStr(" Material\n"), CompleteStr("usemtl"))));
+        assert_eq!(keyword(CompleteStr("f#comment\n")), Ok((CompleteStr("#comment\n"), CompleteStr("f"))));
+    }
+
+    #[test]
+    fn test_parse_unknown_statement() {
+        let input = CompleteStr("bevel on # vendor\nf 1 2 3\n");
+        assert_eq!(unknown_statement(input), Ok((CompleteStr("f 1 2 3\n"), (CompleteStr("bevel"), Cow::Borrowed("on")))));
+
+        let input = CompleteStr("trace_obj\n");
+        assert_eq!(unknown_statement(input), Ok((CompleteStr(""), (CompleteStr("trace_obj"), Cow::Borrowed("")))));
+
+        let input = CompleteStr("x-tag 1 \\\n 2 # vendor\n");
+        assert_eq!(unknown_statement(input), Ok((CompleteStr(""), (CompleteStr("x-tag"), Cow::Owned("1 2".to_string())))));
+    }
+}
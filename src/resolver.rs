@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Finds and opens the files a model refers to, such as `mtllib` libraries and texture maps.
+pub trait ResourceResolver {
+    /// Where `name`, as written in the resource at `base`, is found.
+    /// By default names are relative to the directory containing `base`.
+    fn resolve(&self, base: &Path, name: &str) -> PathBuf {
+        base.parent().unwrap_or_else(|| Path::new("")).join(name)
+    }
+
+    /// Opens the resource at `path`. A resource that does not exist is an `io::ErrorKind::NotFound` error.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// The path `path` really refers to once symbolic links are followed, used to keep
+    /// sandboxed loads inside their root. `None` if it does not exist or the resolver has no links.
+    fn canonicalize(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Opens resources from the file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemResolver;
+
+impl ResourceResolver for FileSystemResolver {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn canonicalize(&self, path: &Path) -> Option<PathBuf> {
+        fs::canonicalize(path).ok()
+    }
+}
+
+/// Opens resources from a map of paths to their contents. `.` and `..` in paths are
+/// resolved before lookup, so `models/../cube.mtl` finds `cube.mtl`.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    resources: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> MemoryResolver {
+        MemoryResolver::default()
+    }
+
+    pub fn insert<P: Into<PathBuf>, D: Into<Vec<u8>>>(&mut self, path: P, data: D) {
+        let path = path.into();
+        let path = normalize(&path).unwrap_or(path);
+        self.resources.insert(path, data.into());
+    }
+}
+
+impl From<HashMap<PathBuf, Vec<u8>>> for MemoryResolver {
+    fn from(resources: HashMap<PathBuf, Vec<u8>>) -> MemoryResolver {
+        let mut resolver = MemoryResolver::new();
+        for (path, data) in resources {
+            resolver.insert(path, data);
+        }
+        resolver
+    }
+}
+
+impl ResourceResolver for MemoryResolver {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let resource = normalize(path).and_then(|path| self.resources.get(&path));
+        match resource {
+            Some(data) => Ok(Box::new(Cursor::new(data.as_slice()))),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("no resource at {}", path.display())))
+        }
+    }
+}
+
+/// Why a `Sandbox` refused a reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SandboxViolation {
+    /// The reference is an absolute path.
+    AbsolutePath,
+    /// The reference resolves to a path outside the sandbox root, usually through `..`.
+    OutsideRoot,
+}
+
+/// Restricts the files a model may reference to a directory, for loading untrusted models.
+/// `check` compares paths as text. Loaders also compare the paths given by
+/// `ResourceResolver::canonicalize`, so a symbolic link inside the root cannot lead out of it.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    root: PathBuf,
+}
+
+// Removes `.` and `..` components without touching the file system.
+// Returns `None` if `..` would go above the start of the path.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                match normalized.components().next_back() {
+                    Some(Component::Normal(_)) => { normalized.pop(); },
+                    _ => return None
+                }
+            },
+            c => normalized.push(c.as_os_str()),
+        }
+    }
+    Some(normalized)
+}
+
+// Joins a relative path to the current directory, so relative and absolute paths can be compared.
+fn absolute(path: &Path) -> PathBuf {
+    match env::current_dir() {
+        Ok(ref dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf()
+    }
+}
+
+fn is_absolute_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    // Windows paths are refused on every platform.
+    let windows_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    Path::new(name).has_root() || name.starts_with('/') || name.starts_with('\\') || windows_drive
+}
+
+impl Sandbox {
+    /// A relative `root` is taken from the current directory.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Sandbox {
+        Sandbox {
+            root: absolute(&root.into()),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Checks `name`, as written in a file, and the path `resolved` it was resolved to.
+    pub fn check(&self, name: &str, resolved: &Path) -> Result<(), SandboxViolation> {
+        if is_absolute_name(name) {
+            return Err(SandboxViolation::AbsolutePath);
+        }
+
+        match (normalize(&self.root), normalize(&absolute(resolved))) {
+            (Some(ref root), Some(ref path)) if path.starts_with(root) => Ok(()),
+            _ => Err(SandboxViolation::OutsideRoot)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_relative_to_base() {
+        let resolver = MemoryResolver::new();
+
+        assert_eq!(resolver.resolve(Path::new("models/cube.obj"), "cube.mtl"), PathBuf::from("models/cube.mtl"));
+        assert_eq!(resolver.resolve(Path::new("cube.obj"), "textures/wood.png"), PathBuf::from("textures/wood.png"));
+    }
+
+    #[test]
+    fn test_memory_resolver_open() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("models/cube.mtl", "newmtl Red\n");
+
+        let mut data = String::new();
+        resolver.open(Path::new("models/cube.mtl")).unwrap().read_to_string(&mut data).unwrap();
+        assert_eq!(data, "newmtl Red\n");
+        assert!(resolver.open(Path::new("models/textures/../cube.mtl")).is_ok());
+
+        match resolver.open(Path::new("models/missing.mtl")) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            Ok(_) => panic!("opened a missing resource")
+        };
+    }
+
+    #[test]
+    fn test_sandbox_check() {
+        let sandbox = Sandbox::new("uploads/42");
+
+        assert_eq!(sandbox.check("cube.mtl", Path::new("uploads/42/cube.mtl")), Ok(()));
+        assert_eq!(sandbox.check("textures/../wood.png", Path::new("uploads/42/textures/../wood.png")), Ok(()));
+        assert_eq!(sandbox.check("/etc/passwd", Path::new("/etc/passwd")), Err(SandboxViolation::AbsolutePath));
+        assert_eq!(sandbox.check("C:\\Windows\\win.ini", Path::new("uploads/42/C:\\Windows\\win.ini")),
+                   Err(SandboxViolation::AbsolutePath));
+        assert_eq!(sandbox.check("../../../etc/passwd", Path::new("uploads/42/../../../etc/passwd")),
+                   Err(SandboxViolation::OutsideRoot));
+        assert_eq!(sandbox.check("../43/cube.mtl", Path::new("uploads/42/../43/cube.mtl")),
+                   Err(SandboxViolation::OutsideRoot));
+    }
+
+    #[test]
+    fn test_sandbox_relative_root() {
+        let sandbox = Sandbox::new("uploads/42");
+        let root = env::current_dir().unwrap().join("uploads/42");
+
+        assert_eq!(sandbox.root(), root.as_path());
+        assert_eq!(sandbox.check("cube.mtl", &root.join("cube.mtl")), Ok(()));
+        assert_eq!(sandbox.check("../43/cube.mtl", &root.join("../43/cube.mtl")), Err(SandboxViolation::OutsideRoot));
+    }
+}
@@ -0,0 +1,214 @@
+use tdmath::Vector3;
+
+/// How polygons with more than three vertices are split into triangles.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Triangulation {
+    /// Fan triangulation for convex polygons, ear clipping for concave ones.
+    #[default]
+    Automatic,
+    /// Fan out from the first vertex. Only correct for convex polygons.
+    Fan,
+    /// Ear clipping. Handles concave polygons at a higher cost.
+    EarClipping,
+}
+
+/// Splits a polygon into triangles, returning indexes into `polygon`.
+pub fn triangulate(polygon: &[Vector3], strategy: Triangulation) -> Vec<[usize; 3]> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+
+    match strategy {
+        Triangulation::Fan => fan(polygon.len()),
+        Triangulation::EarClipping => ear_clip(polygon),
+        Triangulation::Automatic => {
+            if is_convex(polygon) {
+                fan(polygon.len())
+            } else {
+                ear_clip(polygon)
+            }
+        }
+    }
+}
+
+fn fan(count: usize) -> Vec<[usize; 3]> {
+    (1..count - 1).map(|i| [0, i, i + 1]).collect()
+}
+
+// Newell's method, which gives a sensible normal for concave and slightly non-planar polygons.
+// The length of the result is twice the polygon's area.
+fn newell_normal(polygon: &[Vector3]) -> (f32, f32, f32) {
+    let mut normal = (0.0f32, 0.0f32, 0.0f32);
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[(i + 1) % polygon.len()];
+        normal.0 += (a.y - b.y) * (a.z + b.z);
+        normal.1 += (a.z - b.z) * (a.x + b.x);
+        normal.2 += (a.x - b.x) * (a.y + b.y);
+    }
+    normal
+}
+
+/// Unit normal of a polygon with counter-clockwise winding, or zero if the polygon is degenerate.
+pub fn face_normal(polygon: &[Vector3]) -> Vector3 {
+    let (x, y, z) = newell_normal(polygon);
+    let length = (x * x + y * y + z * z).sqrt();
+    if length > 0.0 {
+        Vector3::new(x / length, y / length, z / length)
+    } else {
+        Vector3::zero()
+    }
+}
+
+// Projects the polygon onto the axis-aligned plane it is most closely aligned with,
+// keeping its winding counter-clockwise.
+fn project(polygon: &[Vector3]) -> Vec<(f32, f32)> {
+    let normal = newell_normal(polygon);
+    let (nx, ny, nz) = (normal.0.abs(), normal.1.abs(), normal.2.abs());
+    polygon.iter().map(|p| {
+        if nx >= ny && nx >= nz {
+            if normal.0 >= 0.0 { (p.y, p.z) } else { (p.z, p.y) }
+        } else if ny >= nz {
+            if normal.1 >= 0.0 { (p.z, p.x) } else { (p.x, p.z) }
+        } else if normal.2 >= 0.0 {
+            (p.x, p.y)
+        } else {
+            (p.y, p.x)
+        }
+    }).collect()
+}
+
+fn cross(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+fn is_convex(polygon: &[Vector3]) -> bool {
+    let points = project(polygon);
+    let n = points.len();
+
+    // The projection keeps the polygon counter-clockwise, so every turn must be to the left.
+    (0..n).all(|i| cross(points[i], points[(i + 1) % n], points[(i + 2) % n]) >= 0.0)
+}
+
+fn inside_triangle(p: (f32, f32), a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+fn ear_clip(polygon: &[Vector3]) -> Vec<[usize; 3]> {
+    let points = project(polygon);
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (prev, cur, next) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (a, b, c) = (points[prev], points[cur], points[next]);
+            if cross(a, b, c) <= 0.0 {
+                return false;
+            }
+
+            remaining.iter()
+                .filter(|&&j| j != prev && j != cur && j != next)
+                .all(|&j| !inside_triangle(points[j], a, b, c))
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+                remaining.remove(i);
+            },
+            None => {
+                // Degenerate or self-intersecting polygon. Fan out whatever is left.
+                for i in 1..n - 1 {
+                    triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+                }
+                return triangles;
+            }
+        }
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Vec<Vector3> {
+        vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ]
+    }
+
+    // An arrow head pointing up, with the notch at index 3.
+    fn arrow() -> Vec<Vector3> {
+        vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 2.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_triangulate_triangle() {
+        let triangle = &square()[..3];
+
+        assert_eq!(triangulate(triangle, Triangulation::Automatic), vec![[0, 1, 2]]);
+        assert_eq!(triangulate(triangle, Triangulation::EarClipping), vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_triangulate_fan() {
+        assert_eq!(triangulate(&square(), Triangulation::Fan), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_triangulate_convex_automatic() {
+        assert_eq!(triangulate(&square(), Triangulation::Automatic), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_triangulate_concave() {
+        let triangles = triangulate(&arrow(), Triangulation::Automatic);
+
+        // The only valid triangulation splits along the diagonal from the tip to the notch.
+        assert_eq!(triangles.len(), 2);
+        for t in &triangles {
+            assert!(t.contains(&1) && t.contains(&3));
+        }
+    }
+
+    #[test]
+    fn test_triangulate_concave_reversed() {
+        let mut polygon = arrow();
+        polygon.reverse();
+
+        let triangles = triangulate(&polygon, Triangulation::EarClipping);
+
+        assert_eq!(triangles.len(), 2);
+        for t in &triangles {
+            assert!(t.contains(&0) && t.contains(&2));
+        }
+    }
+
+    #[test]
+    fn test_triangulate_vertical_plane() {
+        let polygon: Vec<Vector3> = square().iter().map(|p| Vector3::new(p.x, 0.0, p.y)).collect();
+
+        assert_eq!(triangulate(&polygon, Triangulation::EarClipping).len(), 2);
+    }
+
+    #[test]
+    fn test_face_normal() {
+        let n = face_normal(&arrow());
+        assert_eq!((n.x, n.y, n.z), (0.0, 0.0, -1.0));
+
+        let n = face_normal(&square());
+        assert_eq!((n.x, n.y, n.z), (0.0, 0.0, 1.0));
+    }
+}